@@ -1,19 +1,110 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub subcommand: Option<Commands>,
+
+    #[command(flatten)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Serve the output directory locally, rebuilding and live reloading pages on change
+    Serve {
+        /// The port to listen on (only bound on localhost)
+        #[arg(long, short, default_value_t = 8000)]
+        port: u16,
+
+        #[command(flatten)]
+        command: Box<Command>,
+    },
+    /// List the names of the bundled syntax highlighting themes and of the custom ones
+    ListThemes {
+        /// The directory to search for `.tmTheme` files
+        #[arg(long)]
+        themes_dir: Option<String>,
+
+        /// Path to the site config [default: ./md2html.toml if it exists]
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiagnosticsFormat {
+    /// Colored messages with the offending source line underlined
+    Human,
+    /// One json object per line, for editors and CI
+    Json,
+}
+
+/// What the feed entries contain.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedMode {
+    /// The first paragraph of the post as plain text
+    Summary,
+    /// The whole post's html
+    Full,
+}
+
+/// How code blocks are colored.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    /// A `style` attribute on every token
+    Inline,
+    /// Classes styled by a `highlight.css` stylesheet generated from the theme and shared by all pages
+    Classes,
+}
+
+/// The date of posts without a `date` in their front matter.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFallback {
+    /// The modification time of the markdown file
+    Mtime,
+    /// The date of the last commit touching the markdown file, or its modification time if it is not committed
+    Git,
+    /// The time of the build
+    Now,
+    /// Fail instead
+    Never,
+}
+
+#[derive(Args)]
 pub struct Command {
     /// The path to the markdown file
+    #[arg(
+        long,
+        short,
+        required_unless_present = "input_dir",
+        conflicts_with = "input_dir"
+    )]
+    pub file_path: Option<String>,
+
+    /// The directory to recursively search for markdown files (the directory structure is mirrored in the output directory)
     #[arg(long, short)]
-    pub file_path: String,
+    pub input_dir: Option<String>,
 
     /// The output directory in which to place files (generated html, logo and styles)
     #[arg(long, short, default_value_t = String::from("out"))]
     pub out_dir: String,
 
-    /// The domain name of the blog to identify external websites
-    #[arg(long, short, default_value_t = String::from("localhost"))]
-    pub domain_name: String,
+    /// The domain name of the blog to identify external websites [default: localhost]
+    #[arg(long, short)]
+    pub domain_name: Option<String>,
 
     /// Output the HTML and Markdown struct debug info
     #[arg(long, short = 'O')]
@@ -23,10 +114,95 @@ pub struct Command {
     #[arg(long, short = 'v')]
     pub verbose: bool,
 
-    #[arg(long, short, default_value_t = String::from("./authors.toml"))]
-    pub authors_db: String,
+    /// [default: ./authors.toml]
+    #[arg(long, short)]
+    pub authors_db: Option<String>,
+
+    /// Path to the stylesheet [default: ./styles.css]
+    #[arg(long, short)]
+    pub style_sheet: Option<String>,
+
+    /// Path to a page template replacing the built-in layout
+    #[arg(long, short)]
+    pub template: Option<String>,
 
-    /// Force overwrite file to the output directory
+    /// Watch the markdown files, authors db and stylesheet and rebuild on change
+    #[arg(long, short)]
+    pub watch: bool,
+
+    /// Build drafts and posts dated in the future (they are still left out of the feeds and index pages)
+    #[arg(long)]
+    pub drafts: bool,
+
+    /// Overwrite html files in the output directory even if they were not generated by md2html
     #[arg(short = 'F', long)]
     pub force: bool,
+
+    /// List the files that would be written or replaced without writing anything
+    #[arg(long, conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// The syntax highlighting theme, see `md2html list-themes` [default: base16-eighties.dark]
+    #[arg(long)]
+    pub theme: Option<String>,
+
+    /// The directory to search for custom `.tmTheme` files, available as themes named after the file
+    #[arg(long)]
+    pub themes_dir: Option<String>,
+
+    /// The syntax highlighting theme used instead of `--theme` when the reader prefers a dark color scheme
+    /// (implies `--highlight classes`)
+    #[arg(long)]
+    pub dark_theme: Option<String>,
+
+    /// The directory to search for `.sublime-syntax` files of languages missing from the bundled syntaxes
+    /// (cached in the directory)
+    #[arg(long)]
+    pub syntaxes_dir: Option<String>,
+
+    /// How code blocks are colored [default: inline]
+    #[arg(long, value_enum)]
+    pub highlight: Option<HighlightMode>,
+
+    /// The key in the authors db of the author of posts without an `author` field (posts have no author otherwise)
+    #[arg(long)]
+    pub default_author: Option<String>,
+
+    /// Path to the site config [default: ./md2html.toml if it exists]
+    #[arg(long, short)]
+    pub config: Option<String>,
+
+    /// What the `feed.xml` and `atom.xml` entries of a directory contain [default: summary]
+    #[arg(long, value_enum)]
+    pub feed_content: Option<FeedMode>,
+
+    /// The date of posts without a `date` in their front matter [default: mtime]
+    #[arg(long, value_enum)]
+    pub date_fallback: Option<DateFallback>,
+
+    /// How to print errors and warnings in the markdown files
+    #[arg(long, value_enum, default_value_t = DiagnosticsFormat::Human)]
+    pub diagnostics_format: DiagnosticsFormat,
+}
+
+impl Command {
+    pub fn domain_name(&self) -> &str {
+        self.domain_name.as_deref().unwrap_or("localhost")
+    }
+
+    pub fn authors_db(&self) -> &str {
+        self.authors_db.as_deref().unwrap_or("./authors.toml")
+    }
+
+    pub fn style_sheet(&self) -> &str {
+        self.style_sheet.as_deref().unwrap_or("./styles.css")
+    }
+
+    pub fn feed_content(&self) -> FeedMode {
+        self.feed_content.unwrap_or(FeedMode::Summary)
+    }
+
+    pub fn date_fallback(&self) -> DateFallback {
+        self.date_fallback.unwrap_or(DateFallback::Mtime)
+    }
 }
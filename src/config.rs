@@ -0,0 +1,357 @@
+use std::{
+    collections::BTreeMap,
+    fs::read_to_string,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use md2html::{
+    utils::len_to_size, Authors, Highlighting, Md2HtmlError, NavLink, RenderOptions, Template,
+};
+use serde::Deserialize;
+use syntect::{highlighting::Theme, parsing::SyntaxSet};
+
+use crate::cmd::{Command, DateFallback, FeedMode, HighlightMode};
+
+/// The config file looked up in the working directory when `--config` is not given.
+const CONFIG_FILE: &str = "md2html.toml";
+
+/// The site config (`md2html.toml`), every key is optional and the command line takes precedence.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    domain: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    feed_content: Option<FeedMode>,
+    date_fallback: Option<DateFallback>,
+    authors_db: Option<String>,
+    style_sheet: Option<String>,
+    template: Option<String>,
+    logo: Option<String>,
+    fonts: Option<Vec<String>>,
+    nav: Option<Vec<NavLink>>,
+    default_author: Option<String>,
+    author_url: Option<String>,
+    wpm: Option<usize>,
+    theme: Option<String>,
+    themes_dir: Option<String>,
+    dark_theme: Option<String>,
+    syntaxes_dir: Option<String>,
+    syntax_aliases: Option<BTreeMap<String, String>>,
+    highlight: Option<HighlightMode>,
+    /// The file the config was read from, `None` without a config file
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// The dotted key (ex: `nav.title`) defined on the line containing `offset`.
+fn key_at(source: &str, offset: usize) -> Option<String> {
+    let mut table = None;
+    let mut line_start = 0;
+
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            table = Some(trimmed.trim_matches(['[', ']']).trim().to_string());
+        }
+
+        if offset < line_start + line.len() {
+            let key = trimmed
+                .split_once('=')
+                .map(|(key, _)| key.trim().to_string())
+                .filter(|key| !key.is_empty() && !key.starts_with('['));
+
+            return match (table, key) {
+                (Some(table), Some(key)) => Some(format!("{table}.{key}")),
+                (None, key) => key,
+                (table, None) => table,
+            };
+        }
+
+        line_start += line.len();
+    }
+
+    None
+}
+
+impl Config {
+    /// Reads the config file given with `--config`, or `md2html.toml` if it exists.
+    pub fn load(path: Option<&str>, logger: &impl Fn(String)) -> Result<Self, Md2HtmlError> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None if Path::new(CONFIG_FILE).is_file() => PathBuf::from(CONFIG_FILE),
+            None => return Ok(Self::default()),
+        };
+
+        let buf = read_to_string(&path).map_err(Md2HtmlError::io(&path))?;
+        logger(format!(
+            "Read ({}) config file \"{}\"",
+            len_to_size(buf.len())?,
+            path.display()
+        ));
+
+        let mut config = toml::from_str::<Self>(&buf).map_err(|e| Md2HtmlError::Config {
+            path: path.clone(),
+            key: e.span().and_then(|span| key_at(&buf, span.start)),
+            message: e.message().to_string(),
+        })?;
+        config.validate(&path)?;
+
+        // Paths in the config are relative to the config file
+        if let Some(dir) = path.parent() {
+            for file in [
+                &mut config.authors_db,
+                &mut config.style_sheet,
+                &mut config.template,
+                &mut config.themes_dir,
+                &mut config.syntaxes_dir,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *file = dir.join(&*file).display().to_string();
+            }
+        }
+
+        logger("Parsed config file".to_string());
+        config.path = Some(path);
+
+        Ok(config)
+    }
+
+    fn validate(&self, path: &Path) -> Result<(), Md2HtmlError> {
+        let invalid = |key: &str, message: String| Md2HtmlError::Config {
+            path: path.to_path_buf(),
+            key: Some(key.to_string()),
+            message,
+        };
+
+        if self.wpm == Some(0) {
+            return Err(invalid("wpm", "must be greater than 0".to_string()));
+        }
+
+        if let Some(author_url) = self.author_url.as_ref().filter(|url| {
+            !url.starts_with('/')
+                || !url.contains("{author}")
+                || !Path::new(url.trim_start_matches('/'))
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+        }) {
+            return Err(invalid(
+                "author_url",
+                format!(
+                    "\"{author_url}\" must be a path starting with `/` containing `{{author}}` and no `..`"
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fills in the options not given on the command line.
+    pub fn merge_into(&mut self, cmd: &mut Command) {
+        cmd.domain_name = cmd.domain_name.take().or(self.domain.take());
+        cmd.authors_db = cmd.authors_db.take().or(self.authors_db.take());
+        cmd.style_sheet = cmd.style_sheet.take().or(self.style_sheet.take());
+        cmd.template = cmd.template.take().or(self.template.take());
+        cmd.feed_content = cmd.feed_content.or(self.feed_content);
+        cmd.date_fallback = cmd.date_fallback.or(self.date_fallback);
+        cmd.default_author = cmd.default_author.take().or(self.default_author.take());
+        cmd.theme = cmd.theme.take().or(self.theme.take());
+        cmd.themes_dir = cmd.themes_dir.take().or(self.themes_dir.take());
+        cmd.highlight = cmd.highlight.or(self.highlight);
+        cmd.dark_theme = cmd.dark_theme.take().or(self.dark_theme.take());
+        cmd.syntaxes_dir = cmd.syntaxes_dir.take().or(self.syntaxes_dir.take());
+    }
+
+    /// Fails if inline highlighting is combined with a dark theme and one of them comes from the
+    /// config, naming the key of the config (conflicting flags are rejected by `main`).
+    pub fn check_highlight(&self, cmd: &Command) -> Result<(), Md2HtmlError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if cmd.highlight.or(self.highlight) != Some(HighlightMode::Inline)
+            || (cmd.dark_theme.is_none() && self.dark_theme.is_none())
+        {
+            return Ok(());
+        }
+
+        let (key, conflict) = match (cmd.highlight, &cmd.dark_theme) {
+            (None, None) => ("dark_theme", "requires `highlight = \"classes\"`"),
+            (Some(_), _) => ("dark_theme", "cannot be used with `--highlight inline`"),
+            (None, Some(_)) => ("highlight", "\"inline\" cannot be used with `--dark-theme`"),
+        };
+
+        Err(Md2HtmlError::Config {
+            path: path.clone(),
+            key: Some(key.to_string()),
+            message: format!("{conflict} (inline styles cannot follow the color scheme)"),
+        })
+    }
+
+    /// The directory of the custom themes, for `md2html list-themes`.
+    pub fn themes_dir(&self) -> Option<&str> {
+        self.themes_dir.as_deref()
+    }
+
+    pub fn render_options(
+        self,
+        cmd: &Command,
+        authors: Authors,
+        template: Template,
+        themes: BTreeMap<String, Theme>,
+        syntaxes: Arc<SyntaxSet>,
+    ) -> RenderOptions {
+        let defaults = RenderOptions::default();
+
+        // Only the classes can follow the color scheme, so they are implied by a dark theme
+        // (explicitly inline highlighting with a dark theme is rejected by `check_highlight`)
+        let highlighting = match (cmd.highlight, &cmd.dark_theme) {
+            (Some(HighlightMode::Classes), _) | (None, Some(_)) => Highlighting::Classes,
+            (Some(HighlightMode::Inline), _) | (None, None) => Highlighting::Inline,
+        };
+
+        // The aliases of the config are added to (or replace) the default ones
+        let mut syntax_aliases = defaults.syntax_aliases;
+        syntax_aliases.extend(self.syntax_aliases.unwrap_or_default());
+
+        RenderOptions {
+            domain: cmd.domain_name().to_string(),
+            site_title: self.title.unwrap_or(defaults.site_title),
+            site_description: self.description.unwrap_or(defaults.site_description),
+            authors,
+            template,
+            logo: self.logo.unwrap_or(defaults.logo),
+            fonts: self.fonts.unwrap_or(defaults.fonts),
+            nav: self.nav.unwrap_or(defaults.nav),
+            default_author: cmd.default_author.clone(),
+            author_url: self.author_url.unwrap_or(defaults.author_url),
+            words_per_minute: self.wpm.unwrap_or(defaults.words_per_minute),
+            theme: cmd.theme.clone().unwrap_or(defaults.theme),
+            themes,
+            highlighting,
+            dark_theme: cmd.dark_theme.clone(),
+            syntaxes,
+            syntax_aliases,
+            // Looked up per file, see `main::fallback_date`
+            fallback_date: None,
+            // Set by `md2html serve`
+            root_relative_urls: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cmd::Cli;
+
+    /// The key of the error reported when parsing `source`, as done by [`Config::load`].
+    fn error_key(source: &str) -> Option<String> {
+        let error = toml::from_str::<Config>(source)
+            .err()
+            .expect("the config is invalid");
+
+        error.span().and_then(|span| key_at(source, span.start))
+    }
+
+    #[test]
+    fn finds_top_level_keys() {
+        let source = "domain = \"example.com\"\nwpm = 200\n";
+
+        assert_eq!(key_at(source, 0).as_deref(), Some("domain"));
+        assert_eq!(
+            key_at(source, source.find("200").unwrap()).as_deref(),
+            Some("wpm")
+        );
+        assert_eq!(key_at(source, source.len()), None);
+    }
+
+    #[test]
+    fn prefixes_keys_with_their_table() {
+        let source = "theme = \"a\"\n\n[syntax_aliases]\nts = \"TypeScript\"\n";
+
+        assert_eq!(
+            key_at(source, source.find("TypeScript").unwrap()).as_deref(),
+            Some("syntax_aliases.ts")
+        );
+        assert_eq!(
+            key_at(source, source.find("[syntax").unwrap()).as_deref(),
+            Some("syntax_aliases")
+        );
+    }
+
+    #[test]
+    fn reports_invalid_nav_entries_with_their_key() {
+        let source = "[[nav]]\ntitle = \"HOME\"\nurl = \"/\"\n\n[[nav]]\ntitle = \"BLOG\"\nlink = \"/blog\"\n";
+
+        assert_eq!(error_key(source).as_deref(), Some("nav.link"));
+    }
+
+    #[test]
+    fn reports_invalid_values_with_their_key() {
+        assert_eq!(error_key("wpm = \"fast\"\n").as_deref(), Some("wpm"));
+        assert_eq!(
+            error_key("highlight = \"both\"\n").as_deref(),
+            Some("highlight")
+        );
+    }
+
+    #[test]
+    fn rejects_author_urls_outside_the_output_directory() {
+        let validate = |author_url: &str| {
+            let config = Config {
+                author_url: Some(author_url.to_string()),
+                ..Config::default()
+            };
+            config.validate(Path::new(CONFIG_FILE)).is_ok()
+        };
+
+        assert!(validate("/authors/@{author}"));
+        assert!(validate("/team/{author}/"));
+        assert!(!validate("authors/{author}"));
+        assert!(!validate("/authors"));
+        assert!(!validate("/../../{author}"));
+        assert!(!validate("/authors/../../{author}"));
+    }
+
+    #[test]
+    fn names_the_config_key_conflicting_with_inline_highlighting() {
+        let config_key = |source: &str, args: &[&str]| {
+            let config = Config {
+                path: Some(PathBuf::from(CONFIG_FILE)),
+                ..toml::from_str(source).unwrap()
+            };
+            let args = ["md2html", "-f", "post.md"].iter().chain(args);
+            let cmd = Cli::parse_from(args).command;
+
+            match config.check_highlight(&cmd) {
+                Err(Md2HtmlError::Config { key, .. }) => key,
+                _ => None,
+            }
+        };
+        let inline = "highlight = \"inline\"\n";
+        let dark = "dark_theme = \"base16-ocean.dark\"\n";
+
+        assert_eq!(
+            config_key(&format!("{inline}{dark}"), &[]).as_deref(),
+            Some("dark_theme")
+        );
+        assert_eq!(
+            config_key(dark, &["--highlight", "inline"]).as_deref(),
+            Some("dark_theme")
+        );
+        assert_eq!(
+            config_key(inline, &["--dark-theme", "base16-ocean.dark"]).as_deref(),
+            Some("highlight")
+        );
+        assert_eq!(config_key(dark, &[]), None);
+        assert_eq!(
+            config_key(&format!("{inline}{dark}"), &["--highlight", "classes"]),
+            None
+        );
+    }
+}
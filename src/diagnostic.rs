@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::Md2HtmlError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A position in the markdown file, lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    /// The number of characters to underline
+    pub length: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Self {
+            line,
+            column,
+            length: length.max(1),
+        }
+    }
+
+    /// The location of a byte offset into `text`, which must start at the beginning of the file.
+    pub fn from_offset(text: &str, offset: usize, length: usize) -> Self {
+        let before = &text[..offset.min(text.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self::new(
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+            length,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    #[serde(flatten)]
+    pub location: Option<Location>,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    /// `None` for errors not tied to a markdown file (ex: an invalid config)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a Path>,
+    #[serde(flatten)]
+    diagnostic: &'a Diagnostic,
+}
+
+impl Diagnostic {
+    pub fn warning(message: String, location: Location) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            location: Some(location),
+        }
+    }
+
+    pub fn info(message: String) -> Self {
+        Self {
+            severity: Severity::Info,
+            message,
+            location: None,
+        }
+    }
+
+    /// Renders the diagnostic for the terminal, underlining its location in `source`.
+    pub fn render(&self, path: Option<&Path>, source: Option<&str>) -> String {
+        let header = match self.severity {
+            Severity::Error => format!("[ERROR]: {}", self.message).bright_red(),
+            Severity::Warning => format!("[WARNING]: {}", self.message).yellow(),
+            Severity::Info => format!("[INFO]: {}", self.message).bright_blue(),
+        };
+
+        let (path, location) = match (path, self.location) {
+            (None, _) => return header.to_string(),
+            (Some(path), None) => {
+                return format!("{header}\n {} {}", "-->".bright_blue(), path.display())
+            }
+            (Some(path), Some(location)) => (path, location),
+        };
+
+        let mut rendered = format!(
+            "{header}\n {} {}:{}:{}",
+            "-->".bright_blue(),
+            path.display(),
+            location.line,
+            location.column
+        );
+
+        if let Some(line) = source.and_then(|source| source.lines().nth(location.line - 1)) {
+            let number = location.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let length = location
+                .length
+                .min(line.chars().count().saturating_sub(location.column - 1))
+                .max(1);
+
+            let carets = match self.severity {
+                Severity::Error => "^".repeat(length).bright_red(),
+                Severity::Warning => "^".repeat(length).yellow(),
+                Severity::Info => "^".repeat(length).bright_blue(),
+            };
+
+            rendered.push_str(&format!(
+                "\n{gutter} {bar}\n{} {bar} {line}\n{gutter} {bar} {}{carets}",
+                number.bright_blue(),
+                " ".repeat(location.column - 1),
+                bar = "|".bright_blue(),
+            ));
+        }
+
+        rendered
+    }
+
+    /// Serializes the diagnostic as a single line of json.
+    pub fn to_json(&self, path: Option<&Path>) -> String {
+        serde_json::to_string(&JsonDiagnostic {
+            file: path,
+            diagnostic: self,
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl From<&Md2HtmlError> for Diagnostic {
+    fn from(error: &Md2HtmlError) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: error.to_string(),
+            location: error.location(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_offsets_by_line_and_character() {
+        let text = "title = \"Café\"\nauthor = \"jdoe\"\n";
+
+        assert_eq!(Location::from_offset(text, 0, 5), Location::new(1, 1, 5));
+        assert_eq!(
+            Location::from_offset(text, text.find("jdoe").unwrap(), 4),
+            Location::new(2, 11, 4)
+        );
+        assert_eq!(
+            Location::from_offset(text, text.find('"').unwrap() + 6, 0),
+            Location::new(1, 14, 1)
+        );
+    }
+
+    #[test]
+    fn underlines_the_location_in_the_source() {
+        colored::control::set_override(false);
+        let diagnostic =
+            Diagnostic::warning("Image has no alt text".to_string(), Location::new(2, 3, 4));
+
+        assert_eq!(
+            diagnostic.render(Some(Path::new("post.md")), Some("# Title\n![](a.png)\n")),
+            "[WARNING]: Image has no alt text\n --> post.md:2:3\n  |\n2 | ![](a.png)\n  |   ^^^^"
+        );
+        assert_eq!(
+            diagnostic.to_json(Some(Path::new("post.md"))),
+            r#"{"file":"post.md","severity":"warning","message":"Image has no alt text","line":2,"column":3,"length":4}"#
+        );
+    }
+}
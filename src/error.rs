@@ -0,0 +1,188 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use crate::diagnostic::Location;
+
+#[derive(Debug)]
+pub enum Md2HtmlError {
+    /// Reading or writing a file failed
+    Io { path: PathBuf, source: io::Error },
+    /// The toml, yaml or json front matter could not be parsed
+    FrontMatter {
+        message: String,
+        location: Option<Location>,
+    },
+    /// The document does not start with a front matter
+    MissingFrontMatter,
+    /// An alias points outside of the output directory, to the post itself or to a generated page
+    InvalidAlias {
+        alias: String,
+        /// Why the alias is rejected (ex: `aliases must stay inside the output directory`)
+        reason: &'static str,
+    },
+    /// Two markdown files generate the same output file
+    OutputCollision {
+        path: PathBuf,
+        /// The markdown file that generated the output first
+        first: PathBuf,
+    },
+    /// Two different tags or authors share a page (ex: `C++` and `C#` are both slugged `c`)
+    PageCollision {
+        /// The url path of the page (ex: `/tags/c`)
+        path: String,
+        /// What the page is generated for (ex: `tag "C"`)
+        first: String,
+        second: String,
+    },
+    /// The front matter has no date and there is no date to fall back to
+    MissingDate,
+    /// The authors db could not be parsed
+    AuthorsDb(Box<toml::de::Error>),
+    /// The post's author is not present in the authors db
+    MissingAuthor {
+        author: String,
+        location: Option<Location>,
+    },
+    /// A footnote label is not a number greater than 0
+    FootnoteLabel {
+        label: String,
+        location: Option<Location>,
+    },
+    /// An emoticon maps to a shortcode without an emoji
+    UnknownShortcode(String),
+    /// Syntax highlighting a code block failed
+    Highlight(syntect::Error),
+    /// Minifying the generated styles failed
+    CssMinify(String),
+    /// A file is too large to be processed
+    TooLarge(usize),
+    /// An existing html file was not generated by md2html and `--force` was not given
+    Overwrite(PathBuf),
+    /// Watching the sources failed
+    Watch(String),
+    /// The preview server could not listen on its address
+    Serve { address: String, message: String },
+    /// The page template could not be parsed or rendered
+    Template(minijinja::Error),
+    /// The syntax highlighting theme does not exist
+    UnknownTheme {
+        theme: String,
+        /// The names of the bundled and custom themes
+        available: Vec<String>,
+    },
+    /// The custom `.sublime-syntax` files could not be loaded
+    Syntaxes { path: PathBuf, message: String },
+    /// A custom `.tmTheme` file could not be loaded
+    Theme {
+        path: PathBuf,
+        error: syntect::LoadingError,
+    },
+    /// A key of the config file is invalid
+    Config {
+        path: PathBuf,
+        /// The offending key (ex: `nav.title`), if known
+        key: Option<String>,
+        message: String,
+    },
+}
+
+impl Md2HtmlError {
+    /// Helper for `map_err` attaching the path to an io error.
+    pub fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::Io { path, source }
+    }
+
+    /// Where in the markdown file the error occurred, if known.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Self::FrontMatter { location, .. }
+            | Self::MissingAuthor { location, .. }
+            | Self::FootnoteLabel { location, .. } => *location,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Md2HtmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "\"{}\": {source}", path.display()),
+            Self::FrontMatter { message, .. } => write!(f, "Invalid front matter: {message}"),
+            Self::MissingFrontMatter => write!(f, "Front matter not found"),
+            Self::MissingDate => write!(f, "The front matter has no `date`"),
+            Self::InvalidAlias { alias, reason } => write!(f, "Invalid alias \"{alias}\" ({reason})"),
+            Self::OutputCollision { path, first } => write!(
+                f,
+                "\"{}\" is already generated from \"{}\" (set a different `slug` or alias)",
+                path.display(),
+                first.display()
+            ),
+            Self::PageCollision {
+                path,
+                first,
+                second,
+            } => write!(
+                f,
+                "{first} and {second} both have the page \"{path}\" (rename one of them)"
+            ),
+            Self::AuthorsDb(e) => write!(f, "Invalid authors db: {e}"),
+            Self::MissingAuthor { author, .. } => {
+                write!(f, "Author \"{author}\" not found in the authors db")
+            }
+            Self::FootnoteLabel { label, .. } => write!(
+                f,
+                "Invalid footnote label \"{label}\" (footnote labels must be numbers starting at 1)"
+            ),
+            Self::UnknownShortcode(shortcode) => write!(f, "Emoji \"{shortcode}\" not found"),
+            Self::Highlight(e) => write!(f, "Failed to highlight code: {e}"),
+            Self::CssMinify(e) => write!(f, "Failed to minify styles: {e}"),
+            Self::TooLarge(len) => write!(f, "Too big to parse ({len} bytes)"),
+            Self::Overwrite(path) => write!(
+                f,
+                "Refusing to overwrite \"{}\" which was not generated by md2html (pass `--force` to overwrite it)",
+                path.display()
+            ),
+            Self::Watch(e) => write!(f, "{e}"),
+            Self::Serve { address, message } => {
+                write!(f, "Failed to serve on \"{address}\": {message}")
+            }
+            Self::Template(e) => write!(f, "Invalid template: {e}"),
+            Self::UnknownTheme { theme, available } => write!(
+                f,
+                "Theme \"{theme}\" not found, expected one of: {}",
+                available.join(", ")
+            ),
+            Self::Syntaxes { path, message } => {
+                write!(f, "\"{}\": Invalid syntax definitions: {message}", path.display())
+            }
+            Self::Theme { path, error } => {
+                write!(f, "\"{}\": Invalid theme: {error}", path.display())
+            }
+            Self::Config {
+                path,
+                key: Some(key),
+                message,
+            } => write!(f, "\"{}\": Invalid key `{key}`: {message}", path.display()),
+            Self::Config { path, message, .. } => write!(f, "\"{}\": {message}", path.display()),
+        }
+    }
+}
+
+impl Error for Md2HtmlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::AuthorsDb(error) => Some(&**error),
+            Self::Highlight(e) => Some(e),
+            Self::Template(e) => Some(e),
+            Self::Theme { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<syntect::Error> for Md2HtmlError {
+    fn from(e: syntect::Error) -> Self {
+        Self::Highlight(e)
+    }
+}
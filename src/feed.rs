@@ -0,0 +1,274 @@
+use chrono::{SecondsFormat, Utc};
+
+use crate::{utils::escape, PostSummary};
+
+/// The channel information shared by the rss and atom feeds.
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    pub domain: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub post: PostSummary,
+    /// The rendered post, or a summary of it
+    pub content: FeedContent,
+}
+
+#[derive(Debug, Clone)]
+pub enum FeedContent {
+    /// Plain text summary of the post
+    Summary(String),
+    /// The post's html
+    Full(String),
+}
+
+/// The entries from newest to oldest.
+fn sorted(entries: &[FeedEntry]) -> Vec<&FeedEntry> {
+    let mut entries = entries.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| {
+        b.post
+            .date
+            .cmp(&a.post.date)
+            .then_with(|| a.post.url.cmp(&b.post.url))
+    });
+
+    entries
+}
+
+/// Generates a RSS 2.0 feed (`feed.xml`).
+pub fn rss(feed: &Feed, entries: &[FeedEntry]) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+        <channel>\n\
+        <title>{}</title>\n\
+        <link>https://{}/</link>\n\
+        <description>{}</description>\n\
+        <generator>md2html</generator>\n\
+        <atom:link href=\"https://{}/feed.xml\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape(&feed.title),
+        feed.domain,
+        escape(&feed.description),
+        feed.domain,
+    );
+
+    if let Some(latest) = sorted(entries).first() {
+        xml.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>\n",
+            latest.post.date.to_rfc2822()
+        ));
+    }
+
+    for entry in sorted(entries) {
+        xml.push_str(&format!(
+            "<item>\n\
+            <title>{}</title>\n\
+            <link>{}</link>\n\
+            <guid isPermaLink=\"true\">{}</guid>\n\
+            <pubDate>{}</pubDate>\n",
+            escape(&entry.post.title),
+            escape(&entry.post.url),
+            escape(&entry.post.url),
+            entry.post.date.to_rfc2822(),
+        ));
+
+        if !entry.post.authors.is_empty() {
+            xml.push_str(&format!(
+                "<dc:creator>{}</dc:creator>\n",
+                escape(&entry.post.author_names())
+            ));
+        }
+
+        // RSS allows a single author, given by email, the names go in `dc:creator`
+        if let Some((author, email)) = entry
+            .post
+            .authors
+            .iter()
+            .find_map(|author| Some((author, author.email.as_ref()?)))
+        {
+            xml.push_str(&format!(
+                "<author>{} ({})</author>\n",
+                escape(email),
+                escape(&author.name)
+            ));
+        }
+
+        for tag in &entry.post.tags {
+            xml.push_str(&format!("<category>{}</category>\n", escape(tag)));
+        }
+
+        let content = match &entry.content {
+            FeedContent::Summary(summary) | FeedContent::Full(summary) => escape(summary),
+        };
+        xml.push_str(&format!("<description>{content}</description>\n</item>\n"));
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+
+    xml
+}
+
+/// Generates an Atom feed (`atom.xml`).
+pub fn atom(feed: &Feed, entries: &[FeedEntry]) -> String {
+    let updated = entries
+        .iter()
+        .map(|entry| entry.post.updated.unwrap_or(entry.post.date))
+        .max()
+        .unwrap_or_else(Utc::now);
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+        <title>{}</title>\n\
+        <subtitle>{}</subtitle>\n\
+        <id>https://{}/</id>\n\
+        <link href=\"https://{}/\"/>\n\
+        <link href=\"https://{}/atom.xml\" rel=\"self\"/>\n\
+        <updated>{}</updated>\n\
+        <generator>md2html</generator>\n",
+        escape(&feed.title),
+        escape(&feed.description),
+        feed.domain,
+        feed.domain,
+        feed.domain,
+        updated.to_rfc3339_opts(SecondsFormat::Secs, true),
+    );
+
+    for entry in sorted(entries) {
+        xml.push_str(&format!(
+            "<entry>\n\
+            <title>{}</title>\n\
+            <id>{}</id>\n\
+            <link href=\"{}\"/>\n\
+            <published>{}</published>\n\
+            <updated>{}</updated>\n",
+            escape(&entry.post.title),
+            escape(&entry.post.url),
+            escape(&entry.post.url),
+            entry.post.date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            entry
+                .post
+                .updated
+                .unwrap_or(entry.post.date)
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        ));
+
+        for author in &entry.post.authors {
+            xml.push_str(&format!(
+                "<author><name>{}</name>{}</author>\n",
+                escape(&author.name),
+                author
+                    .email
+                    .as_ref()
+                    .map_or_else(String::new, |email| format!(
+                        "<email>{}</email>",
+                        escape(email)
+                    )),
+            ));
+        }
+
+        for tag in &entry.post.tags {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape(tag)));
+        }
+
+        match &entry.content {
+            FeedContent::Summary(summary) => {
+                xml.push_str(&format!("<summary>{}</summary>\n", escape(summary)))
+            }
+            FeedContent::Full(html) => xml.push_str(&format!(
+                "<content type=\"html\">{}</content>\n",
+                escape(html)
+            )),
+        }
+
+        xml.push_str("</entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::PostAuthor;
+
+    fn entry(title: &str, url: &str, day: u32, content: FeedContent) -> FeedEntry {
+        FeedEntry {
+            post: PostSummary {
+                title: title.to_string(),
+                url: url.to_string(),
+                tags: vec!["C++".to_string()],
+                authors: vec![PostAuthor {
+                    key: "jdoe".to_string(),
+                    name: "Jane <Doe>".to_string(),
+                    email: Some("jane@example.com".to_string()),
+                }],
+                date: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+                updated: None,
+                reading_time: 1,
+            },
+            content,
+        }
+    }
+
+    fn feed() -> Feed {
+        Feed {
+            title: "Tom & Jerry".to_string(),
+            description: String::new(),
+            domain: "example.com".to_string(),
+        }
+    }
+
+    #[test]
+    fn escapes_the_rss_feed() {
+        let entries = [
+            entry(
+                "Old",
+                "https://example.com/old",
+                1,
+                FeedContent::Summary("1 < 2".to_string()),
+            ),
+            entry(
+                "New & shiny",
+                "https://example.com/new?a=1&b=2",
+                2,
+                FeedContent::Summary(String::new()),
+            ),
+        ];
+        let xml = rss(&feed(), &entries);
+
+        assert!(xml.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(xml.contains("<link>https://example.com/new?a=1&amp;b=2</link>"));
+        assert!(xml.contains("<dc:creator>Jane &lt;Doe&gt;</dc:creator>"));
+        assert!(xml.contains("<author>jane@example.com (Jane &lt;Doe&gt;)</author>"));
+        assert!(xml.contains("<category>C++</category>"));
+        assert!(xml.contains("<description>1 &lt; 2</description>"));
+        assert!(xml.find("New &amp; shiny") < xml.find("<title>Old</title>"));
+        assert!(xml.contains("<lastBuildDate>Tue, 2 Jan 2024 00:00:00 +0000</lastBuildDate>"));
+    }
+
+    #[test]
+    fn escapes_the_atom_feed() {
+        let entries = [entry(
+            "A <b>bold</b> title",
+            "https://example.com/post",
+            1,
+            FeedContent::Full("<p>Hello</p>".to_string()),
+        )];
+        let xml = atom(&feed(), &entries);
+
+        assert!(xml.contains("<title>A &lt;b&gt;bold&lt;/b&gt; title</title>"));
+        assert!(xml.contains(
+            "<author><name>Jane &lt;Doe&gt;</name><email>jane@example.com</email></author>"
+        ));
+        assert!(xml.contains("<content type=\"html\">&lt;p&gt;Hello&lt;/p&gt;</content>"));
+        assert!(xml.contains("<updated>2024-01-01T00:00:00Z</updated>"));
+    }
+}
@@ -0,0 +1,320 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use fancy_regex::Regex;
+use once_cell::sync::Lazy;
+use serde::de::IgnoredAny;
+use serde_json::Value;
+
+use crate::{diagnostic::Location, utils::FrontMatter, Md2HtmlError};
+
+/// A `{{ page.extra.key }}` reference in the body, the key being a dotted path (ex: `series.name`).
+static EXTRA_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{\s*page\.extra\.([\w-]+(?:\.[\w-]+)*)\s*\}\}").expect("the regex is valid")
+});
+
+/// The key toml datetimes are serialized under when deserialized into something else than a datetime.
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// The languages the front matter can be written in, detected from its first line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Delimited with `+++`
+    Toml,
+    /// Delimited with `---`
+    Yaml,
+    /// Delimited with `;;;`, or an object starting the file without delimiters
+    Json,
+}
+
+impl Format {
+    /// The format of the front matter starting `markdown` along with its delimiter,
+    /// `None` if the document does not start with a front matter.
+    pub(crate) fn detect(markdown: &str) -> Option<(Self, Option<&'static str>)> {
+        let first_line = markdown.lines().next()?.trim_end();
+
+        match first_line {
+            "+++" => Some((Self::Toml, Some("+++"))),
+            "---" => Some((Self::Yaml, Some("---"))),
+            ";;;" => Some((Self::Json, Some(";;;"))),
+            _ if first_line.starts_with('{') => Some((Self::Json, None)),
+            _ => None,
+        }
+    }
+}
+
+/// The length of the json object starting `markdown`, up to the end of the line closing it.
+pub(crate) fn json_len(markdown: &str) -> usize {
+    let mut objects = serde_json::Deserializer::from_str(markdown).into_iter::<IgnoredAny>();
+    let end = match objects.next() {
+        Some(Ok(_)) => objects.byte_offset(),
+        // Invalid json is reported when parsing the front matter, up to the first closing brace line
+        _ => markdown.find("\n}").map_or(markdown.len(), |i| i + 2),
+    };
+
+    markdown[end..]
+        .find('\n')
+        .map_or(markdown.len(), |i| end + i + 1)
+}
+
+/// The front matter without its delimiters, along with its offset into `raw`.
+fn content<'a>(raw: &'a str, delimiter: Option<&str>) -> (usize, &'a str) {
+    let Some(delimiter) = delimiter else {
+        return (0, raw);
+    };
+
+    let start = raw.find('\n').map_or(raw.len(), |i| i + 1);
+    let mut end = start;
+    for line in raw[start..].split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            return (start, &raw[start..end]);
+        }
+        end += line.len();
+    }
+
+    (start, &raw[start..])
+}
+
+/// Removes the ` at line X column Y` suffix of yaml and json errors, the location being shown separately.
+fn message(error: &impl ToString) -> String {
+    let message = error.to_string();
+    match message.rsplit_once(" at line ") {
+        Some((message, _)) => message.to_string(),
+        None => message,
+    }
+}
+
+/// Parses the raw front matter (including its delimiters), which must start the file.
+pub(crate) fn parse(raw: &str) -> Result<FrontMatter, Md2HtmlError> {
+    let (format, delimiter) = Format::detect(raw).ok_or(Md2HtmlError::MissingFrontMatter)?;
+    let (offset, content) = content(raw, delimiter);
+
+    // The front matter always starts the file, so offsets into it are offsets into the file
+    let error = |message: String, start: Option<usize>, length: usize| Md2HtmlError::FrontMatter {
+        message,
+        location: start.map(|start| Location::from_offset(raw, offset + start, length)),
+    };
+
+    match format {
+        Format::Toml => toml::from_str(content)
+            .map(|mut front_matter: FrontMatter| {
+                front_matter
+                    .extra
+                    .values_mut()
+                    .for_each(toml_datetimes_to_strings);
+                front_matter
+            })
+            .map_err(|e| {
+                error(
+                    e.message().to_string(),
+                    e.span().map(|span| span.start),
+                    e.span().map_or(1, |span| span.len()),
+                )
+            }),
+        Format::Yaml => serde_yaml::from_str(content).map_err(|e| {
+            error(
+                message(&e),
+                e.location().map(|location| location.index()),
+                1,
+            )
+        }),
+        Format::Json => serde_json::from_str(content).map_err(|e| {
+            let start = (e.line() > 0).then(|| {
+                content
+                    .split_inclusive('\n')
+                    .take(e.line() - 1)
+                    .map(str::len)
+                    .sum::<usize>()
+                    + e.column().saturating_sub(1)
+            });
+
+            error(message(&e), start, 1)
+        }),
+    }
+}
+
+/// Replaces the toml datetimes in an `extra` field by their text (ex: `2024-01-31`).
+fn toml_datetimes_to_strings(value: &mut Value) {
+    match value {
+        Value::Object(map) if map.len() == 1 => match map.get(TOML_DATETIME_KEY) {
+            Some(Value::String(datetime)) => *value = Value::String(datetime.clone()),
+            _ => map.values_mut().for_each(toml_datetimes_to_strings),
+        },
+        Value::Object(map) => map.values_mut().for_each(toml_datetimes_to_strings),
+        Value::Array(values) => values.iter_mut().for_each(toml_datetimes_to_strings),
+        _ => {}
+    }
+}
+
+/// Replaces the `{{ page.extra.key }}` references with the value of the `extra` field (strings as is,
+/// other values as json), returning the keys and byte ranges of the references to unknown fields.
+pub(crate) fn interpolate(
+    text: &str,
+    extra: &BTreeMap<String, Value>,
+) -> (String, Vec<(String, Range<usize>)>) {
+    let mut interpolated = String::with_capacity(text.len());
+    let mut unknown = Vec::new();
+    let mut last = 0;
+
+    for captures in EXTRA_REGEX.captures_iter(text).flatten() {
+        let (Some(reference), Some(key)) = (captures.get(0), captures.get(1)) else {
+            continue;
+        };
+
+        let mut path = key.as_str().split('.');
+        let value = path
+            .next()
+            .and_then(|field| extra.get(field))
+            .and_then(|value| {
+                path.try_fold(value, |value, key| match value {
+                    Value::Object(map) => map.get(key),
+                    Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+                    _ => None,
+                })
+            });
+
+        interpolated.push_str(&text[last..reference.start()]);
+        match value {
+            Some(Value::String(string)) => interpolated.push_str(string),
+            Some(value) => interpolated.push_str(&value.to_string()),
+            None => {
+                interpolated.push_str(reference.as_str());
+                unknown.push((key.as_str().to_string(), reference.range()));
+            }
+        }
+        last = reference.end();
+    }
+    interpolated.push_str(&text[last..]);
+
+    (interpolated, unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_format_from_the_first_line() {
+        assert_eq!(
+            Format::detect("+++\ntitle = \"A\"\n+++\n"),
+            Some((Format::Toml, Some("+++")))
+        );
+        assert_eq!(
+            Format::detect("---\r\ntitle: A\r\n---\r\n"),
+            Some((Format::Yaml, Some("---")))
+        );
+        assert_eq!(
+            Format::detect(";;;\n{\"title\": \"A\"}\n;;;\n"),
+            Some((Format::Json, Some(";;;")))
+        );
+        assert_eq!(
+            Format::detect("{\"title\": \"A\"}\n# Heading\n"),
+            Some((Format::Json, None))
+        );
+        assert_eq!(Format::detect("# Heading\n"), None);
+        assert_eq!(Format::detect(""), None);
+    }
+
+    #[test]
+    fn json_len_stops_at_the_line_closing_the_object() {
+        let markdown = "{\n  \"title\": \"A }\",\n  \"tags\": [\"rust\"]\n}\n# Heading\n";
+        assert_eq!(
+            &markdown[..json_len(markdown)],
+            &markdown[..markdown.len() - 10]
+        );
+
+        let markdown = "{\"title\": \"A\"}   \nBody";
+        assert_eq!(&markdown[..json_len(markdown)], "{\"title\": \"A\"}   \n");
+
+        assert_eq!(json_len("{\"title\": \"A\"}"), 14);
+    }
+
+    #[test]
+    fn json_len_of_invalid_json_falls_back_to_the_first_closing_brace_line() {
+        let markdown = "{\n  \"title\": A\n}\n# Heading\n";
+        assert_eq!(&markdown[..json_len(markdown)], "{\n  \"title\": A\n}\n");
+
+        let markdown = "{\n  \"title\": A\n";
+        assert_eq!(json_len(markdown), markdown.len());
+    }
+
+    #[test]
+    fn parses_a_json_object_starting_the_file() {
+        let raw = "{\"title\": \"A\", \"tags\": [\"rust\"], \"cover\": \"/a.png\"}\n";
+        let front_matter = parse(&raw[..json_len(raw)]).unwrap();
+
+        assert_eq!(front_matter.title, "A");
+        assert_eq!(front_matter.tags, ["rust"]);
+        assert_eq!(front_matter.extra["cover"], "/a.png");
+    }
+
+    #[test]
+    fn locates_front_matter_errors_in_the_file() {
+        let error = parse("---\ntitle: A\ntags: [rust\n---\n").unwrap_err();
+        assert!(matches!(
+            error,
+            Md2HtmlError::FrontMatter {
+                location: Some(Location { line, .. }),
+                ..
+            } if line > 2
+        ));
+
+        let error = parse("+++\ntitle = \"A\"\ntags = 1\n+++\n").unwrap_err();
+        assert!(matches!(
+            error,
+            Md2HtmlError::FrontMatter {
+                location: Some(Location { line: 3, .. }),
+                ..
+            }
+        ));
+    }
+
+    fn extra() -> BTreeMap<String, Value> {
+        serde_json::from_value(serde_json::json!({
+            "description": "A post",
+            "part": 2,
+            "a": { "b": "nested" },
+            "series": { "name": "Rust", "posts": ["intro", "traits"] },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn interpolates_extra_fields() {
+        let (text, unknown) = interpolate(
+            "{{ page.extra.description }}, part {{page.extra.part}}",
+            &extra(),
+        );
+
+        assert_eq!(text, "A post, part 2");
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn interpolates_nested_paths() {
+        let (text, unknown) = interpolate(
+            "{{ page.extra.series.name }} ({{ page.extra.series.posts.1 }}), {{ page.extra.a.b }}",
+            &extra(),
+        );
+
+        assert_eq!(text, "Rust (traits), nested");
+        assert!(unknown.is_empty());
+
+        let (text, _) = interpolate("{{ page.extra.series }}", &extra());
+        assert_eq!(text, r#"{"name":"Rust","posts":["intro","traits"]}"#);
+    }
+
+    #[test]
+    fn keeps_references_to_unknown_fields() {
+        let text = "See {{ page.extra.missing }} and {{ page.extra.series.name.first }}";
+        let (interpolated, unknown) = interpolate(text, &extra());
+
+        assert_eq!(interpolated, text);
+        assert_eq!(
+            unknown,
+            [
+                ("missing".to_string(), 4..28),
+                ("series.name.first".to_string(), 33..text.len()),
+            ]
+        );
+    }
+}
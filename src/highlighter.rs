@@ -1,68 +1,297 @@
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
 use crate::html::{Meta, Tag};
-use crate::utils::must;
+use crate::{Highlighting, Md2HtmlError};
 
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{Color, FontStyle, Style, Theme};
-use syntect::parsing::SyntaxSet;
+use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
 use syntect::util::LinesWithEndings;
 
-pub fn highlight_code(code: &str, lang: &str, ps: &SyntaxSet, theme: &Theme) -> Tag {
+/// The prefix of the token classes, so that they do not clash with the page's classes.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// The stylesheet of the token classes emitted with [`Highlighting::Classes`] for `theme`.
+///
+/// With a dark theme, each theme only applies to its color scheme: the reader's preference
+/// unless the `<html>` element has the `light` or `dark` class (ex: set by a toggle).
+pub fn stylesheet(theme: &Theme, dark_theme: Option<&Theme>) -> Result<String, Md2HtmlError> {
+    let light = theme_css(theme)?;
+    let Some(dark_theme) = dark_theme else {
+        return Ok(light);
+    };
+    let dark = theme_css(dark_theme)?;
+
+    Ok(format!(
+        "@media (prefers-color-scheme: light) {{\n{}}}\n\n{}\n\
+        @media (prefers-color-scheme: dark) {{\n{}}}\n\n{}",
+        scoped(&light, "html:not(.dark)"),
+        scoped(&light, "html.light"),
+        scoped(&dark, "html:not(.light)"),
+        scoped(&dark, "html.dark"),
+    ))
+}
+
+/// The token classes of a theme along with its background of the lines highlighted with `hl_lines`.
+fn theme_css(theme: &Theme) -> Result<String, Md2HtmlError> {
+    let mut css = css_for_theme_with_class_style(theme, CLASS_STYLE)?;
+    if let Some(Color { r, g, b, a }) = theme.settings.line_highlight {
+        css.push_str(&format!(
+            ".hl-code > code > div.code-line-highlighted {{\n background-color: #{r:02x}{g:02x}{b:02x}{a:02x};\n}}\n"
+        ));
+    }
+
+    Ok(css)
+}
+
+/// Prefixes every selector of a stylesheet generated by syntect with `scope`.
+fn scoped(css: &str, scope: &str) -> String {
+    let mut scoped = String::new();
+    for rule in css.split_inclusive('}') {
+        let Some((selectors, body)) = rule.split_once('{') else {
+            continue;
+        };
+        // Drops the comment naming the theme
+        let selectors = selectors
+            .rfind("*/")
+            .map_or(selectors, |end| &selectors[end + 2..]);
+
+        let selectors = selectors
+            .split(',')
+            .map(|selector| format!("{scope} {}", selector.trim()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        scoped.push_str(&format!("{selectors} {{{body}\n"));
+    }
+
+    scoped
+}
+
+/// The spans of a line, each token being styled inline.
+fn inline_spans(
+    h: &mut HighlightLines,
+    line: &str,
+    ps: &SyntaxSet,
+) -> Result<Vec<Tag>, Md2HtmlError> {
+    let mut spans = Vec::new();
+    for (
+        Style {
+            foreground: Color { r, g, b, a },
+            font_style,
+            ..
+        },
+        text,
+    ) in h.highlight_line(line, ps)?
+    {
+        let mut style = format!("style=\"color: #{r:02x}{g:02x}{b:02x}{a:02x};");
+
+        if font_style.contains(FontStyle::BOLD) {
+            style.push_str(" font-weight: 700;");
+        }
+        if font_style.contains(FontStyle::ITALIC) {
+            style.push_str(" font-style: italic;");
+        }
+        if font_style.contains(FontStyle::UNDERLINE) {
+            style.push_str(" text-decoration: underline;");
+        }
+
+        style.push('"');
+
+        spans.push(Tag::Span(
+            Meta::new()
+                .with_child(Tag::Text(text.to_string()))
+                .with_attr(&style),
+        ))
+    }
+
+    Ok(spans)
+}
+
+/// The spans of a line, each scope being a span with its classes.
+///
+/// Scopes spanning several lines are closed at the end of the line and reopened on the next one,
+/// so that every line stays balanced.
+fn classed_spans(
+    parse_state: &mut ParseState,
+    scopes: &mut ScopeStack,
+    line: &str,
+    ps: &SyntaxSet,
+) -> Result<Tag, Md2HtmlError> {
+    let mut html = String::new();
+    for scope in scopes.as_slice() {
+        let classes = scope
+            .build_string()
+            .split('.')
+            .map(|atom| format!("hl-{atom}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        html.push_str(&format!("<span class=\"{classes}\">"));
+    }
+
+    let ops = parse_state
+        .parse_line(line, ps)
+        .map_err(syntect::Error::from)?;
+    html.push_str(&line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, scopes)?.0);
+    html.push_str(&"</span>".repeat(scopes.len()));
+
+    Ok(Tag::Raw(html))
+}
+
+/// The language and attributes of a fenced code block, parsed from its info string
+/// (ex: `rust title="src/main.rs" hl_lines="3-5 9" linenos=false start=42`).
+#[derive(Debug)]
+pub struct CodeInfo {
+    pub lang: String,
+    /// Shown above the code (ex: the file name)
+    pub title: Option<String>,
+    /// The lines to highlight, counted from the first line of the block whatever `start` is
+    pub hl_lines: Vec<RangeInclusive<usize>>,
+    /// Whether to number the lines, by default unless the code is plain text
+    pub line_numbers: Option<bool>,
+    /// The number of the first line
+    pub start: usize,
+}
+
+impl CodeInfo {
+    /// Parses an info string, returning the messages of the invalid attributes along with it.
+    pub fn parse(info: &str) -> (Self, Vec<String>) {
+        let mut code_info = Self {
+            lang: String::new(),
+            title: None,
+            hl_lines: Vec::new(),
+            line_numbers: None,
+            start: 1,
+        };
+        let mut invalid = Vec::new();
+
+        let mut rest = info.trim();
+        // The language is the first word unless it is already an attribute
+        let lang_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if !rest[..lang_end].contains('=') {
+            code_info.lang = rest[..lang_end].to_string();
+            rest = rest[lang_end..].trim_start();
+        }
+
+        while !rest.is_empty() {
+            let key_end = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = &rest[key_end..];
+
+            let Some(value) = rest.strip_prefix('=') else {
+                invalid.push(format!("Code block attribute `{key}` has no value"));
+                rest = rest.trim_start();
+                continue;
+            };
+
+            let (value, remaining) = match value.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => value.split_at(value.find(char::is_whitespace).unwrap_or(value.len())),
+            };
+            rest = remaining.trim_start();
+
+            match key {
+                "title" => code_info.title = Some(value.to_string()),
+                "hl_lines" => match parse_lines(value) {
+                    Some(lines) => code_info.hl_lines = lines,
+                    None => invalid.push(format!(
+                        "Invalid `hl_lines` \"{value}\" (expected line numbers and ranges like \"3-5 9\")"
+                    )),
+                },
+                "linenos" => match value.parse() {
+                    Ok(line_numbers) => code_info.line_numbers = Some(line_numbers),
+                    Err(_) => invalid.push(format!(
+                        "Invalid `linenos` \"{value}\" (expected `true` or `false`)"
+                    )),
+                },
+                // Bounded so that the numbers of the following lines fit too
+                "start" => match value.parse::<u32>() {
+                    Ok(start) => code_info.start = start as usize,
+                    Err(_) => invalid.push(format!(
+                        "Invalid `start` \"{value}\" (expected a line number)"
+                    )),
+                },
+                _ => invalid.push(format!("Unknown code block attribute `{key}`")),
+            }
+        }
+
+        (code_info, invalid)
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.hl_lines.iter().any(|lines| lines.contains(&line))
+    }
+}
+
+/// Parses line numbers and ranges separated by spaces or commas (ex: `3-5 9`).
+fn parse_lines(lines: &str) -> Option<Vec<RangeInclusive<usize>>> {
+    lines
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|range| !range.is_empty())
+        .map(|range| match range.split_once('-') {
+            Some((start, end)) => Some(start.parse().ok()?..=end.parse().ok()?),
+            None => range.parse().ok().map(|line| line..=line),
+        })
+        .collect()
+}
+
+/// The syntax of a code block's language, looked up by extension or name after resolving aliases.
+pub fn find_syntax<'a>(
+    ps: &'a SyntaxSet,
+    aliases: &BTreeMap<String, String>,
+    lang: &str,
+) -> Option<&'a SyntaxReference> {
+    let lang = aliases.get(lang).map_or(lang, String::as_str);
+
+    ps.find_syntax_by_token(lang)
+        .or_else(|| ps.find_syntax_by_name(lang))
+}
+
+/// Highlights a code block with `syntax`, or as plain text if it is `None`.
+pub fn highlight_code(
+    code: &str,
+    syntax: Option<&SyntaxReference>,
+    info: &CodeInfo,
+    ps: &SyntaxSet,
+    theme: &Theme,
+    highlighting: Highlighting,
+) -> Result<Tag, Md2HtmlError> {
     let mut children = Vec::new();
 
     let mut is_plain_text = false;
 
-    let syntax = ps.find_syntax_by_token(lang).unwrap_or_else(|| {
+    let syntax = syntax.unwrap_or_else(|| {
         is_plain_text = true;
         ps.find_syntax_plain_text()
     });
 
     let mut h = HighlightLines::new(syntax, theme);
+    let mut parse_state = ParseState::new(syntax);
+    let mut scopes = ScopeStack::new();
+
+    let line_numbers = info.line_numbers.unwrap_or(!is_plain_text);
 
     let lines = LinesWithEndings::from(code).collect::<Vec<_>>();
     let n = lines.len();
 
-    let width = n.to_string().len();
+    let width = info.start.saturating_add(n.max(1) - 1).to_string().len();
 
     for (line_number, line) in lines.iter().enumerate() {
-        let mut cur_line_children = Vec::new();
-        for (
-            Style {
-                foreground: Color { r, g, b, a },
-                font_style,
-                ..
-            },
-            text,
-        ) in must(h.highlight_line(line, &ps))
-        {
-            let mut style = b"style=\"".to_vec();
-            style.extend(format!("color: #{r:02x}{g:02x}{b:02x}{a:02x};").as_bytes());
-
-            if font_style.contains(FontStyle::BOLD) {
-                style.extend(b" font-weight: 700;");
-            }
-            if font_style.contains(FontStyle::ITALIC) {
-                style.extend(b" font-style: italic;");
-            }
-            if font_style.contains(FontStyle::UNDERLINE) {
-                style.extend(b" text-decoration: underline;");
-            }
-
-            style.push(b'"');
+        let cur_line_children = match highlighting {
+            Highlighting::Inline => inline_spans(&mut h, line, ps)?,
+            Highlighting::Classes => vec![classed_spans(&mut parse_state, &mut scopes, line, ps)?],
+        };
 
-            cur_line_children.push(Tag::Span(
-                Meta::new()
-                    .with_child(Tag::Text(text.to_string()))
-                    .with_attr(&must(String::from_utf8(style))),
-            ))
-        }
-
-        children.push(Tag::Div(Meta::new().with_children(if is_plain_text {
-            vec![Tag::Div(Meta::new().with_children(cur_line_children))]
-        } else {
+        let line_meta = Meta::new().with_children(if line_numbers {
             vec![
                 Tag::Span(
                     Meta::new()
-                        .with_children(vec![Tag::Text(format!("{:>width$}.", line_number + 1))])
+                        .with_children(vec![Tag::Text(format!(
+                            "{:>width$}.",
+                            info.start.saturating_add(line_number)
+                        ))])
                         .with_attr("class=\"code-line-number\""),
                 ),
                 Tag::Div(
@@ -71,10 +300,27 @@ pub fn highlight_code(code: &str, lang: &str, ps: &SyntaxSet, theme: &Theme) ->
                         .with_attr(&format!("style=\"padding-left: {}px\"", 25 + width * 10)),
                 ),
             ]
-        })))
+        } else {
+            vec![Tag::Div(Meta::new().with_children(cur_line_children))]
+        });
+
+        children.push(Tag::Div(if info.is_highlighted(line_number + 1) {
+            line_meta.with_attr("class=\"code-line-highlighted\"")
+        } else {
+            line_meta
+        }));
+    }
+
+    let mut attrs = vec![format!(
+        "style=\"padding: {}px 20px 20px 20px\"",
+        if is_plain_text { 20 } else { 40 }
+    )];
+    if highlighting == Highlighting::Classes {
+        // The theme's colors from the stylesheet
+        attrs.push(String::from("class=\"hl-code\""));
     }
 
-    Tag::Pre(
+    Ok(Tag::Pre(
         Meta::new()
             .with_children(vec![
                 if !is_plain_text {
@@ -82,11 +328,169 @@ pub fn highlight_code(code: &str, lang: &str, ps: &SyntaxSet, theme: &Theme) ->
                 } else {
                     Tag::Empty
                 },
+                match &info.title {
+                    Some(title) => Tag::Span(
+                        Meta::new()
+                            .with_child(Tag::Text(title.clone()))
+                            .with_attr("class=\"code-title\""),
+                    ),
+                    None => Tag::Empty,
+                },
                 Tag::Code(Meta::new().with_children(children)),
             ])
-            .with_attr(&format!(
-                "style=\"padding: {}px 20px 20px 20px\"",
-                if is_plain_text { 20 } else { 40 }
-            )),
-    )
+            .with_attrs(attrs),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_and_unquoted_attributes() {
+        let (info, invalid) =
+            CodeInfo::parse(r#"rust title="src/main.rs" hl_lines="3-5 9" linenos=false start=42"#);
+
+        assert_eq!(info.lang, "rust");
+        assert_eq!(info.title.as_deref(), Some("src/main.rs"));
+        assert_eq!(info.hl_lines, [3..=5, 9..=9]);
+        assert_eq!(info.line_numbers, Some(false));
+        assert_eq!(info.start, 42);
+        assert!(invalid.is_empty());
+    }
+
+    #[test]
+    fn parses_quoted_values_with_spaces() {
+        let (info, invalid) = CodeInfo::parse(r#"toml title="Cargo manifest" hl_lines=2"#);
+
+        assert_eq!(info.title.as_deref(), Some("Cargo manifest"));
+        assert_eq!(info.hl_lines, [2..=2]);
+        assert!(invalid.is_empty());
+    }
+
+    #[test]
+    fn parses_attributes_without_a_language() {
+        let (info, invalid) = CodeInfo::parse("title=notes.txt");
+
+        assert_eq!(info.lang, "");
+        assert_eq!(info.title.as_deref(), Some("notes.txt"));
+        assert_eq!(info.start, 1);
+        assert!(invalid.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_attributes() {
+        let (info, invalid) =
+            CodeInfo::parse(r#"rust hl_lines="3-x" linenos=maybe start=-1 wrap=true title"#);
+        let (_, too_large) = CodeInfo::parse("rust start=18446744073709551615");
+
+        assert_eq!(info.lang, "rust");
+        assert!(info.hl_lines.is_empty());
+        assert_eq!(info.line_numbers, None);
+        assert_eq!(info.start, 1);
+        assert_eq!(
+            invalid,
+            [
+                r#"Invalid `hl_lines` "3-x" (expected line numbers and ranges like "3-5 9")"#,
+                r#"Invalid `linenos` "maybe" (expected `true` or `false`)"#,
+                r#"Invalid `start` "-1" (expected a line number)"#,
+                "Unknown code block attribute `wrap`",
+                "Code block attribute `title` has no value",
+            ]
+        );
+        assert_eq!(
+            too_large,
+            [r#"Invalid `start` "18446744073709551615" (expected a line number)"#]
+        );
+    }
+
+    #[test]
+    fn finds_syntaxes_through_their_aliases() {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let aliases = crate::RenderOptions::default().syntax_aliases;
+        let name = |lang: &str| find_syntax(&ps, &aliases, lang).map(|syntax| syntax.name.as_str());
+
+        assert_eq!(name("rs"), Some("Rust"));
+        assert_eq!(name("Rust"), Some("Rust"));
+        assert_eq!(name("console"), name("bash"));
+        assert!(name("bash").is_some());
+        assert_eq!(name("brainfudge"), None);
+    }
+
+    #[test]
+    fn numbers_lines_up_to_the_largest_start() {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let theme = &syntect::highlighting::ThemeSet::load_defaults().themes["InspiredGitHub"];
+        let info = CodeInfo {
+            start: usize::MAX,
+            ..CodeInfo::parse("").0
+        };
+
+        assert!(highlight_code("a\nb\n", None, &info, &ps, theme, Highlighting::Inline).is_ok());
+    }
+
+    #[test]
+    fn parses_line_numbers_and_ranges() {
+        assert_eq!(parse_lines("3-5 9"), Some(vec![3..=5, 9..=9]));
+        assert_eq!(parse_lines("1,4-6,  8"), Some(vec![1..=1, 4..=6, 8..=8]));
+        assert_eq!(parse_lines(""), Some(vec![]));
+        assert_eq!(parse_lines("3-"), None);
+        assert_eq!(parse_lines("a"), None);
+        assert_eq!(parse_lines("1-2-3"), None);
+    }
+
+    #[test]
+    fn balances_the_classed_spans_of_every_line() {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let syntax = ps.find_syntax_by_token("rust").unwrap();
+        let mut parse_state = ParseState::new(syntax);
+        let mut scopes = ScopeStack::new();
+
+        for (i, line) in LinesWithEndings::from("/* a\n b */\nfn main() {}\n").enumerate() {
+            let Tag::Raw(html) = classed_spans(&mut parse_state, &mut scopes, line, &ps).unwrap()
+            else {
+                panic!("a line is raw html");
+            };
+
+            assert!(html.starts_with("<span class=\"hl-source hl-rust\">"));
+            assert_eq!(
+                html.matches("<span").count(),
+                html.matches("</span>").count()
+            );
+            assert!(!html.contains("style="));
+            // The comment is reopened on its second line
+            assert_eq!(html.contains("hl-comment"), i < 2);
+        }
+    }
+
+    #[test]
+    fn switches_themes_with_the_color_scheme() {
+        let themes = syntect::highlighting::ThemeSet::load_defaults().themes;
+        let (light, dark) = (&themes["base16-ocean.light"], &themes["base16-ocean.dark"]);
+
+        let css = stylesheet(light, None).unwrap();
+        assert_eq!(css, theme_css(light).unwrap());
+        assert!(!css.contains("@media"));
+
+        let css = stylesheet(light, Some(dark)).unwrap();
+        let (light_css, dark_css) = css
+            .split_once("@media (prefers-color-scheme: dark)")
+            .unwrap();
+        assert!(light_css.starts_with("@media (prefers-color-scheme: light)"));
+        assert!(light_css.contains("html:not(.dark) .hl-code"));
+        assert!(light_css.contains("html.light .hl-code"));
+        assert!(dark_css.contains("html:not(.light) .hl-code"));
+        assert!(dark_css.contains("html.dark .hl-code"));
+        assert!(!dark_css.contains("html.light"));
+    }
+
+    #[test]
+    fn scopes_every_selector() {
+        let css = "/*\n * theme \"Test\"\n */\n.hl-code {\n color: #fff;\n}\n.hl-a, .hl-b {\n color: #000;\n}\n";
+
+        assert_eq!(
+            scoped(css, "html.dark"),
+            "html.dark .hl-code {\n color: #fff;\n}\nhtml.dark .hl-a, html.dark .hl-b {\n color: #000;\n}\n"
+        );
+    }
 }
@@ -1,10 +1,3 @@
-use std::{
-    error::Error,
-    io::{BufWriter, Write},
-};
-
-use crate::utils::must;
-
 #[derive(Debug, Default, Clone)]
 pub struct Meta {
     attrs: Vec<String>,
@@ -171,21 +164,47 @@ impl Tag {
         tag.to_string()
     }
 
-    pub fn write_recursive(&self, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    /// The child tags, empty for tags without a [`Meta`].
+    pub fn children(&self) -> &[Tag] {
+        use self::Tag::*;
+        match self {
+            Head(meta) | Title(meta) | Link(meta) | Meta(meta) | Body(meta) | H1(meta)
+            | H2(meta) | H3(meta) | H4(meta) | H5(meta) | H6(meta) | P(meta) | Hr(meta)
+            | Pre(meta) | Blockquote(meta) | Ol(meta) | Ul(meta) | Li(meta) | Dl(meta)
+            | Dt(meta) | Dd(meta) | Div(meta) | Table(meta) | Tr(meta) | Th(meta) | Td(meta)
+            | Img(meta) | A(meta) | I(meta) | B(meta) | S(meta) | Sub(meta) | Sup(meta)
+            | Code(meta) | Mark(meta) | Span(meta) | Br(meta) | Section(meta) | U(meta)
+            | Details(meta) | Summary(meta) | Thead(meta) | Tbody(meta) | Figure(meta)
+            | Figcaption(meta) | Nav(meta) => &meta.children,
+
+            Doctype(_) | Html(_, _) | Comment(_) | Text(_) | Raw(_) | Style(_) | Empty => &[],
+        }
+    }
+
+    /// The text of the tag and all its descendants.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            _ => self.children().iter().map(Tag::text).collect(),
+        }
+    }
+
+    /// Appends the html of the tag and its children to `html`.
+    pub fn write_recursive(&self, html: &mut String) {
         use self::Tag::*;
         match self {
             Doctype(children) => {
-                write!(writer, "<!DOCTYPE html>")?;
+                html.push_str("<!DOCTYPE html>");
                 for child in children {
-                    child.write_recursive(writer)?;
+                    child.write_recursive(html);
                 }
             }
 
             Html(head, body) => {
-                write!(writer, "<html lang=\"en\">")?;
-                head.write_recursive(writer)?;
-                body.write_recursive(writer)?;
-                write!(writer, "</html>")?;
+                html.push_str("<html lang=\"en\">");
+                head.write_recursive(html);
+                body.write_recursive(html);
+                html.push_str("</html>");
             }
 
             Head(meta) | Title(meta) | H1(meta) | H2(meta) | H3(meta) | H4(meta) | H5(meta)
@@ -195,46 +214,49 @@ impl Tag {
             | Pre(meta) | B(meta) | S(meta) | Sub(meta) | Sup(meta) | Mark(meta) | A(meta)
             | U(meta) | Details(meta) | Summary(meta) | Tbody(meta) | Figcaption(meta)
             | Figure(meta) | Nav(meta) => {
-                write!(writer, "<{}", self.tag_name())?;
+                html.push('<');
+                html.push_str(&self.tag_name());
                 for attr in &meta.attrs {
-                    write!(writer, " {attr}")?;
+                    html.push(' ');
+                    html.push_str(attr);
                 }
-                write!(writer, ">")?;
+                html.push('>');
                 for child in &meta.children {
-                    child.write_recursive(writer)?;
+                    child.write_recursive(html);
                 }
-                write!(writer, "</{}>", self.tag_name())?;
+                html.push_str("</");
+                html.push_str(&self.tag_name());
+                html.push('>');
             }
 
             Link(meta) | Meta(meta) | Hr(meta) | Br(meta) | Img(meta) => {
-                write!(writer, "<{}", self.tag_name())?;
+                html.push('<');
+                html.push_str(&self.tag_name());
                 for attr in &meta.attrs {
-                    write!(writer, " {attr}")?;
+                    html.push(' ');
+                    html.push_str(attr);
                 }
-                write!(writer, ">")?;
+                html.push('>');
             }
 
-            Text(s) => write!(writer, "{}", s.replace("<", "&lt;").replace("<", "&gt;"))?,
+            Text(s) => html.push_str(&s.replace("<", "&lt;").replace("<", "&gt;")),
 
-            Comment(comment) => writeln!(writer, "\n<!-- {comment} -->")?,
+            Comment(comment) => html.push_str(&format!("\n<!-- {comment} -->\n")),
 
-            Style(s) => write!(writer, "<style>{s}</style>")?,
+            Style(s) => html.push_str(&format!("<style>{s}</style>")),
 
-            Raw(s) => write!(writer, "{s}")?,
+            Raw(s) => html.push_str(s),
 
             Empty => {}
         }
-
-        Ok(())
     }
 }
 
 impl Tag {
-    pub fn to_html(&self) -> Vec<u8> {
-        let mut writer = BufWriter::new(Vec::new());
-
-        must(self.write_recursive(&mut writer));
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        self.write_recursive(&mut html);
 
-        must(writer.into_inner())
+        html
     }
 }
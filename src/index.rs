@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    html::{Meta, Tag},
+    template::{self, Page},
+    utils::{tag_url, text_to_slug},
+    Md2HtmlError, PostSummary, RenderOptions,
+};
+
+/// Posts grouped by the slug of their tags, along with the tag as written in the first post,
+/// failing if tags differing by more than their case share a slug or if a tag has an empty slug.
+pub fn group_by_tag(
+    posts: &[PostSummary],
+) -> Result<BTreeMap<String, (String, Vec<PostSummary>)>, Md2HtmlError> {
+    let mut tags = BTreeMap::<String, (String, Vec<PostSummary>)>::new();
+    for post in posts {
+        for tag in &post.tags {
+            let slug = text_to_slug(tag);
+            if slug.is_empty() {
+                return Err(Md2HtmlError::FrontMatter {
+                    message: format!(
+                        "tag \"{tag}\" of \"{}\" has no letters or digits to name its page",
+                        post.title
+                    ),
+                    location: None,
+                });
+            }
+
+            let (first, tagged) = tags
+                .entry(slug.clone())
+                .or_insert_with(|| (tag.clone(), Vec::new()));
+
+            if first.to_lowercase() != tag.to_lowercase() {
+                return Err(Md2HtmlError::PageCollision {
+                    path: format!("/tags/{slug}"),
+                    first: format!("tag \"{first}\""),
+                    second: format!("tag \"{tag}\""),
+                });
+            }
+            tagged.push(post.clone());
+        }
+    }
+
+    Ok(tags)
+}
+
+/// The posts from newest to oldest.
+fn post_list(posts: &[PostSummary]) -> Tag {
+    let mut posts = posts.iter().collect::<Vec<_>>();
+    posts.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.title.cmp(&b.title)));
+
+    Tag::Ul(
+        Meta::new()
+            .with_children(
+                posts
+                    .into_iter()
+                    .map(|post| {
+                        Tag::Li(Meta::new().with_children(vec![
+                            Tag::A(
+                                Meta::new()
+                                    .with_child(Tag::Text(post.title.clone()))
+                                    .with_attr(&format!("href=\"{}\"", post.url)),
+                            ),
+                            Tag::Span(Meta::new().with_child(Tag::Text(
+                                [
+                                    post.date.format("%e %B, %Y").to_string(),
+                                    format!("{} min read", post.reading_time),
+                                    post.author_names(),
+                                ]
+                                .into_iter()
+                                .filter(|part| !part.is_empty())
+                                .collect::<Vec<_>>()
+                                .join(" &nbsp;&bull;&nbsp; "),
+                            ))),
+                        ]))
+                    })
+                    .collect(),
+            )
+            .with_attr("class=\"post-list\""),
+    )
+}
+
+/// Renders a page through the template, dating it with its newest post (if it has any).
+fn render_page(
+    page: Page,
+    posts: &[PostSummary],
+    options: &RenderOptions,
+) -> Result<String, Md2HtmlError> {
+    let date = posts.iter().map(|post| post.date).max();
+
+    Ok(template::with_generator_comment(
+        options.template.render(&page)?,
+        date.as_ref(),
+    ))
+}
+
+/// Renders the page listing the posts tagged with `tag` (`/tags/{tag}`).
+pub fn render_tag_page(
+    tag: &str,
+    posts: &[PostSummary],
+    options: &RenderOptions,
+) -> Result<String, Md2HtmlError> {
+    let page = Page {
+        body: template::html([&Tag::Section(Meta::new().with_child(post_list(posts)))]),
+        ..Page::new("tag", format!("#{tag}"), options)
+    };
+
+    render_page(page, posts, options)
+}
+
+/// Renders the page of the author with the key `key` in the authors db (`/authors/@{key}` by default),
+/// listing their posts below their profile.
+pub fn render_author_page(
+    key: &str,
+    posts: &[PostSummary],
+    options: &RenderOptions,
+) -> Result<String, Md2HtmlError> {
+    let author = options
+        .authors
+        .get(key)
+        .ok_or_else(|| Md2HtmlError::MissingAuthor {
+            author: key.to_string(),
+            location: None,
+        })?;
+
+    let posts = posts
+        .iter()
+        .filter(|post| post.authors.iter().any(|author| author.key == key))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let page = Page {
+        body: template::html([&Tag::Section(Meta::new().with_child(post_list(&posts)))]),
+        ..Page::new("author", author.name.clone(), options)
+            .with_authors(&[(key.to_string(), author)], options)
+    };
+
+    render_page(page, &posts, options)
+}
+
+/// Renders the page listing every tag along with its number of posts (`/tags`).
+pub fn render_tags_page(
+    posts: &[PostSummary],
+    options: &RenderOptions,
+) -> Result<String, Md2HtmlError> {
+    let tags = group_by_tag(posts)?
+        .into_values()
+        .map(|(tag, posts)| {
+            Tag::Li(Meta::new().with_children(vec![
+                Tag::A(
+                    Meta::new()
+                        .with_child(Tag::Text(format!("#{tag}")))
+                        .with_attrs(vec![
+                            format!("href=\"{}\"", tag_url(options, &tag)),
+                            "class=\"tag\"".to_string(),
+                        ]),
+                ),
+                Tag::Span(Meta::new().with_child(Tag::Text(format!(
+                    "{} post{}",
+                    posts.len(),
+                    if posts.len() == 1 { "" } else { "s" }
+                )))),
+            ]))
+        })
+        .collect();
+
+    let page = Page {
+        body: template::html([&Tag::Section(
+            Meta::new().with_child(Tag::Ul(
+                Meta::new()
+                    .with_children(tags)
+                    .with_attr("class=\"tag-list\""),
+            )),
+        )]),
+        ..Page::new("tags", String::from("Tags"), options)
+    };
+
+    render_page(page, posts, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn post(title: &str, tags: &[&str]) -> PostSummary {
+        PostSummary {
+            title: title.to_string(),
+            url: format!("/{}", text_to_slug(title)),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            authors: Vec::new(),
+            date: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            updated: None,
+            reading_time: 1,
+        }
+    }
+
+    #[test]
+    fn groups_tags_by_slug_ignoring_case() {
+        let posts = [post("A", &["Rust", "web dev"]), post("B", &["rust"])];
+        let tags = group_by_tag(&posts).unwrap();
+
+        assert_eq!(
+            tags.iter()
+                .map(|(slug, (tag, posts))| (slug.as_str(), tag.as_str(), posts.len()))
+                .collect::<Vec<_>>(),
+            [("rust", "Rust", 2), ("web-dev", "web dev", 1)]
+        );
+    }
+
+    #[test]
+    fn rejects_tags_sharing_a_page() {
+        let posts = [post("A", &["C++"]), post("B", &["C#"])];
+
+        assert!(matches!(
+            group_by_tag(&posts),
+            Err(Md2HtmlError::PageCollision { path, first, second })
+                if path == "/tags/c" && first == "tag \"C++\"" && second == "tag \"C#\""
+        ));
+    }
+
+    #[test]
+    fn rejects_tags_without_a_slug() {
+        let posts = [post("A", &["rust"]), post("B", &["日本語"])];
+
+        assert!(matches!(
+            group_by_tag(&posts),
+            Err(Md2HtmlError::FrontMatter { message, .. }) if message.contains("\"日本語\" of \"B\"")
+        ));
+    }
+
+    #[test]
+    fn lists_the_posts_of_the_author() {
+        let options = RenderOptions {
+            authors: toml::from_str("jdoe = [\"Jane Doe\", \"/jane.png\"]").unwrap(),
+            template: template::Template::new("page.html", "{{ author }} {{ avatar }} {{ body }}")
+                .unwrap(),
+            ..RenderOptions::default()
+        };
+        let mut posts = [post("Mine", &[]), post("Theirs", &[])];
+        posts[0].authors.push(crate::PostAuthor {
+            key: "jdoe".to_string(),
+            name: "Jane Doe".to_string(),
+            email: None,
+        });
+
+        let page = render_author_page("jdoe", &posts, &options).unwrap();
+        assert!(page.contains("Jane Doe /jane.png"));
+        assert!(page.contains("href=\"/mine\""));
+        assert!(!page.contains("Theirs"));
+
+        assert!(matches!(
+            render_author_page("someone", &posts, &options),
+            Err(Md2HtmlError::MissingAuthor { author, .. }) if author == "someone"
+        ));
+    }
+
+    #[test]
+    fn leaves_pages_without_posts_undated() {
+        let options = RenderOptions {
+            template: template::Template::new("page.html", "{{ title }}").unwrap(),
+            ..RenderOptions::default()
+        };
+        let page = render_tags_page(&[], &options).unwrap();
+
+        assert!(template::is_generated(&page));
+        assert!(page.ends_with("(github.com/blood-rogue)`. -->\nTags"));
+    }
+}
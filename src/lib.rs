@@ -0,0 +1,1250 @@
+//! Markdown to HTML conversion used by the `md2html` CLI, see [`render`].
+
+pub mod diagnostic;
+mod error;
+pub mod feed;
+mod front_matter;
+mod highlighter;
+pub mod html;
+pub mod index;
+mod replacer;
+pub mod template;
+pub mod utils;
+
+use std::{collections::BTreeMap, fs, io, path::Path, sync::Arc, time::SystemTime};
+
+use chrono::{DateTime, Utc};
+use comrak::{
+    nodes::{AstNode, ListType, NodeValue, TableAlignment},
+    Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions, ComrakRenderOptions,
+};
+
+use diagnostic::{Diagnostic, Location};
+use html::{Meta, Tag};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+};
+
+pub use error::Md2HtmlError;
+pub use template::Template;
+pub use utils::{Author, Authors, FrontMatter};
+
+static THEME_SET: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
+static SYNTAX_SET: Lazy<Arc<SyntaxSet>> =
+    Lazy::new(|| Arc::new(SyntaxSet::load_defaults_newlines()));
+
+/// The file name of the cached syntax set in the syntaxes directory, see [`load_syntaxes`].
+const SYNTAX_CACHE: &str = ".md2html-syntaxes.packdump";
+
+/// Options controlling how a markdown document is rendered.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// The domain name of the blog to identify external websites
+    pub domain: String,
+    /// The name of the blog, used by the feeds
+    pub site_title: String,
+    pub site_description: String,
+    /// The authors db in which the post's author is looked up
+    pub authors: Authors,
+    /// The layout the rendered markdown is placed in
+    pub template: Template,
+    /// Url of the logo shown in the navbar and used as favicon
+    pub logo: String,
+    /// Google fonts families to load (ex: `Open Sans:wght@300..800`)
+    pub fonts: Vec<String>,
+    pub nav: Vec<NavLink>,
+    /// The author of posts without an `author` field, they have no author if `None`
+    pub default_author: Option<String>,
+    /// The path of the author pages, `{author}` being replaced by the slug of the author's key
+    pub author_url: String,
+    /// The reading speed used for the reading time
+    pub words_per_minute: usize,
+    /// The name of the syntax highlighting theme, see [`theme_names`]
+    pub theme: String,
+    /// The themes `theme` is looked up in, the bundled ones by default (see [`load_themes`])
+    pub themes: BTreeMap<String, Theme>,
+    /// The theme used instead of `theme` in dark mode, only with [`Highlighting::Classes`]
+    pub dark_theme: Option<String>,
+    /// How code blocks are colored
+    pub highlighting: Highlighting,
+    /// The syntax definitions code blocks are highlighted with (see [`load_syntaxes`])
+    pub syntaxes: Arc<SyntaxSet>,
+    /// Languages of code blocks mapped to the name or extension of a syntax (ex: `ts` to `TypeScript`)
+    pub syntax_aliases: BTreeMap<String, String>,
+    /// The date of posts without a `date` in their front matter, which are rejected if it is `None`
+    pub fallback_date: Option<DateTime<Utc>>,
+    /// Links to the blog's pages are root-relative (ex: `/tags/rust`) instead of starting with
+    /// `https://{domain}`, for the preview server
+    pub root_relative_urls: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            domain: String::from("localhost"),
+            site_title: String::new(),
+            site_description: String::new(),
+            authors: Authors::default(),
+            template: Template::default(),
+            logo: String::from("/logo.png"),
+            fonts: [
+                "Jetbrains Mono:wght@100..800",
+                "Open Sans:wght@300..800",
+                "Mulish:wght@200..700",
+                "Roboto Mono:wght@100",
+            ]
+            .map(String::from)
+            .to_vec(),
+            nav: [
+                ("HOME", "/"),
+                ("BLOG", "/blog"),
+                ("ABOUT", "/about"),
+                ("CONTACT", "/contact"),
+            ]
+            .map(|(title, url)| NavLink {
+                title: title.to_string(),
+                url: url.to_string(),
+            })
+            .to_vec(),
+            default_author: None,
+            author_url: String::from("/authors/@{author}"),
+            words_per_minute: 120,
+            theme: String::from("base16-eighties.dark"),
+            themes: THEME_SET.themes.clone(),
+            dark_theme: None,
+            highlighting: Highlighting::Inline,
+            syntaxes: Arc::clone(&SYNTAX_SET),
+            syntax_aliases: [("console", "bash"), ("shell", "bash"), ("terminal", "bash")]
+                .into_iter()
+                .map(|(alias, syntax)| (alias.to_string(), syntax.to_string()))
+                .collect(),
+            fallback_date: None,
+            root_relative_urls: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The reading time in minutes of a post.
+    pub(crate) fn reading_time(&self, word_count: usize) -> usize {
+        word_count / self.words_per_minute.max(1)
+    }
+
+    /// The path of an author's page, `key` being the author's key in the authors db.
+    pub fn author_path(&self, key: &str) -> String {
+        self.author_url
+            .replace("{author}", &utils::text_to_slug(key))
+    }
+
+    /// The url of a page of the blog, `path` starting with `/`.
+    pub fn url(&self, path: &str) -> String {
+        if self.root_relative_urls {
+            path.to_string()
+        } else {
+            self.absolute_url(path)
+        }
+    }
+
+    /// The url of a page of the blog including the domain, whatever `root_relative_urls` is.
+    pub fn absolute_url(&self, path: &str) -> String {
+        format!("https://{}{path}", self.domain)
+    }
+
+    /// The url of an author's page.
+    pub fn author_url(&self, key: &str) -> String {
+        self.url(&self.author_path(key))
+    }
+
+    /// The syntax highlighting theme named `theme`, failing with the available names if it does not exist.
+    pub fn theme(&self) -> Result<&Theme, Md2HtmlError> {
+        self.find_theme(&self.theme)
+    }
+
+    /// The theme named `dark_theme`, if any.
+    pub fn dark_theme(&self) -> Result<Option<&Theme>, Md2HtmlError> {
+        self.dark_theme
+            .as_deref()
+            .map(|theme| self.find_theme(theme))
+            .transpose()
+    }
+
+    fn find_theme(&self, theme: &str) -> Result<&Theme, Md2HtmlError> {
+        self.themes
+            .get(theme)
+            .ok_or_else(|| Md2HtmlError::UnknownTheme {
+                theme: theme.to_string(),
+                available: theme_names(&self.themes)
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            })
+    }
+}
+
+/// How the tokens of code blocks are colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Highlighting {
+    /// A `style` attribute on every token
+    #[default]
+    Inline,
+    /// `hl-` prefixed classes styled by a stylesheet shared by all pages (see [`highlight_stylesheet`])
+    Classes,
+}
+
+/// The path the [`highlight_stylesheet`] is linked from when highlighting with classes.
+pub const HIGHLIGHT_STYLESHEET: &str = "/highlight.css";
+
+/// The stylesheet of the selected theme for [`Highlighting::Classes`], switching to the dark theme
+/// (if any) when the reader prefers a dark color scheme or the `<html>` element has the `dark` class.
+pub fn highlight_stylesheet(options: &RenderOptions) -> Result<String, Md2HtmlError> {
+    highlighter::stylesheet(options.theme()?, options.dark_theme()?)
+}
+
+/// A link in the navbar.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NavLink {
+    pub title: String,
+    pub url: String,
+}
+
+/// The names of the syntax highlighting themes, in alphabetical order.
+pub fn theme_names(themes: &BTreeMap<String, Theme>) -> Vec<&str> {
+    themes.keys().map(String::as_str).collect()
+}
+
+/// The bundled syntax highlighting themes along with the `.tmTheme` files found in `dir`
+/// (recursively), named after their file name (ex: `dracula.tmTheme` is `dracula`).
+pub fn load_themes(dir: Option<&Path>) -> Result<BTreeMap<String, Theme>, Md2HtmlError> {
+    let mut themes = THEME_SET.themes.clone();
+
+    if let Some(dir) = dir {
+        let paths = ThemeSet::discover_theme_paths(dir).map_err(|error| Md2HtmlError::Theme {
+            path: dir.to_path_buf(),
+            error,
+        })?;
+
+        for path in paths {
+            let theme = ThemeSet::get_theme(&path).map_err(|error| Md2HtmlError::Theme {
+                path: path.clone(),
+                error,
+            })?;
+            if let Some(name) = path.file_stem() {
+                themes.insert(name.to_string_lossy().into_owned(), theme);
+            }
+        }
+    }
+
+    Ok(themes)
+}
+
+/// The bundled syntax definitions along with the `.sublime-syntax` files found in `dir` (recursively).
+///
+/// Building the syntaxes is slow, so the merged set is cached as a binary dump in `dir`,
+/// which is rebuilt when a definition is added, removed or modified. An outdated cache is
+/// only replaced if `write_cache` is true (ex: not for a dry run).
+pub fn load_syntaxes(
+    dir: Option<&Path>,
+    write_cache: bool,
+) -> Result<Arc<SyntaxSet>, Md2HtmlError> {
+    let Some(dir) = dir else {
+        return Ok(Arc::clone(&SYNTAX_SET));
+    };
+
+    let invalid = |message: String| Md2HtmlError::Syntaxes {
+        path: dir.to_path_buf(),
+        message,
+    };
+
+    let cache = dir.join(SYNTAX_CACHE);
+    let newest = newest_modification(dir).map_err(|e| invalid(e.to_string()))?;
+    let is_fresh = fs::metadata(&cache)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|cached| cached >= newest);
+    if is_fresh {
+        // An outdated or corrupted cache is rebuilt
+        if let Ok(syntaxes) = syntect::dumps::from_uncompressed_dump_file(&cache) {
+            return Ok(Arc::new(syntaxes));
+        }
+    }
+
+    let mut builder = SyntaxSet::clone(&SYNTAX_SET).into_builder();
+    builder
+        .add_from_folder(dir, true)
+        .map_err(|e| invalid(e.to_string()))?;
+    let syntaxes = builder.build();
+
+    // The cache is only an optimization, the syntaxes are built again if it cannot be written
+    if write_cache {
+        let _ = syntect::dumps::dump_to_uncompressed_file(&syntaxes, &cache);
+    }
+
+    Ok(Arc::new(syntaxes))
+}
+
+/// The last time a `.sublime-syntax` file or a directory (for removed files) in `dir` was modified.
+fn newest_modification(dir: &Path) -> io::Result<SystemTime> {
+    let mut newest = fs::metadata(dir)?.modified()?;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let modified = if path.is_dir() {
+            newest_modification(&path)?
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "sublime-syntax")
+        {
+            fs::metadata(&path)?.modified()?
+        } else {
+            continue;
+        };
+
+        newest = newest.max(modified);
+    }
+
+    Ok(newest)
+}
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: u8,
+    /// The slug used in the heading's anchor (`#heading__{id}`)
+    pub id: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Footnote {
+    pub label: String,
+    /// The rendered content of the footnote definition
+    pub html: String,
+}
+
+/// The result of rendering a markdown document.
+#[derive(Debug, Clone)]
+pub struct RenderedDocument {
+    /// The complete html page
+    pub html: String,
+    pub front_matter: FrontMatter,
+    pub headings: Vec<Heading>,
+    pub word_count: usize,
+    /// The reading time in minutes
+    pub reading_time: usize,
+    /// Ordered by label like on the page, whatever the order they are defined in
+    pub footnotes: Vec<Footnote>,
+    pub warnings: Vec<Diagnostic>,
+    /// The publication date shown on the page
+    pub date: DateTime<Utc>,
+    /// Whether the post is a draft or scheduled for a future front matter `date`, drafts are
+    /// excluded from the feeds and index pages
+    pub draft: bool,
+    /// The html tree of the rendered markdown, without the page layout
+    pub ast: Tag,
+}
+
+/// A converted post, as listed on the index pages and in the feeds.
+#[derive(Debug, Clone)]
+pub struct PostSummary {
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+    pub authors: Vec<PostAuthor>,
+    pub date: DateTime<Utc>,
+    /// When the post was last updated, if given in the front matter
+    pub updated: Option<DateTime<Utc>>,
+    /// The reading time in minutes
+    pub reading_time: usize,
+}
+
+/// An author of a converted post.
+#[derive(Debug, Clone)]
+pub struct PostAuthor {
+    /// The key of the author in the authors db
+    pub key: String,
+    /// The display name
+    pub name: String,
+    pub email: Option<String>,
+}
+
+impl PostSummary {
+    /// The display names of the authors, separated by commas.
+    pub fn author_names(&self) -> String {
+        self.authors
+            .iter()
+            .map(|author| author.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl RenderedDocument {
+    /// The text of the first paragraph, used as the post's summary.
+    pub fn summary(&self) -> String {
+        fn first_paragraph(tag: &Tag) -> Option<&Tag> {
+            match tag {
+                Tag::P(_) => Some(tag),
+                _ => tag.children().iter().find_map(first_paragraph),
+            }
+        }
+
+        first_paragraph(&self.ast)
+            .map(Tag::text)
+            .unwrap_or_default()
+    }
+}
+
+/// Replaces the `{{ page.extra.key }}` references in the text or url of `node` with the
+/// `extra` front matter fields, warning about the unknown ones.
+fn interpolate<'a>(text: &str, node: &'a AstNode<'a>, state: &mut utils::State) -> String {
+    let Some(front_matter) = &state.front_matter else {
+        return text.to_string();
+    };
+
+    let (interpolated, unknown) = front_matter::interpolate(text, &front_matter.extra);
+
+    let data = node.data.borrow();
+    let start = data.sourcepos.start;
+    for (key, range) in unknown {
+        let location = match data.value {
+            NodeValue::Text(_) => Location::new(
+                start.line,
+                start.column + text[..range.start].chars().count(),
+                text[range].chars().count(),
+            ),
+            // The url is somewhere after the start of the link
+            _ => Location::new(start.line, start.column, 1),
+        };
+
+        state.warnings.push(Diagnostic::warning(
+            format!("Unknown front matter field \"{key}\", leaving it as is"),
+            location,
+        ));
+    }
+
+    interpolated
+}
+
+fn iter_nodes<'a>(node: &'a AstNode<'a>, state: &mut utils::State) -> Result<Tag, Md2HtmlError> {
+    Ok(match &node.data.borrow().value {
+        NodeValue::Document => Tag::Section(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::FrontMatter(front_matter) => {
+            state.front_matter = Some(front_matter::parse(front_matter)?);
+            state.front_matter_source.clone_from(front_matter);
+
+            Tag::Empty
+        }
+
+        NodeValue::BlockQuote => Tag::Blockquote(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::List(list) => {
+            let children = node
+                .children()
+                .map(|child| iter_nodes(child, state))
+                .collect::<Result<_, _>>()?;
+
+            match list.list_type {
+                ListType::Bullet => Tag::Ul(Meta::new().with_children(children)),
+                ListType::Ordered => Tag::Ol(Meta::new().with_children(children)),
+            }
+        }
+
+        NodeValue::Item(_) => Tag::Li(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::DescriptionList => {
+            let mut children = Vec::new();
+            for item in node.children() {
+                for child in item.children() {
+                    children.push(iter_nodes(child, state)?);
+                }
+            }
+
+            Tag::Dl(Meta::new().with_children(children))
+        }
+
+        NodeValue::DescriptionItem(_) => Tag::Empty,
+
+        NodeValue::DescriptionTerm => Tag::Dt(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::DescriptionDetails => Tag::Dd(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::CodeBlock(code_block) => {
+            let start = node.data.borrow().sourcepos.start;
+            let (info, invalid) = highlighter::CodeInfo::parse(&code_block.info);
+            let syntax =
+                highlighter::find_syntax(&state.syntaxes, &state.syntax_aliases, &info.lang);
+
+            for message in invalid {
+                state.warnings.push(Diagnostic::warning(
+                    message,
+                    Location::new(
+                        start.line,
+                        start.column,
+                        code_block.fence_length + code_block.info.len(),
+                    ),
+                ));
+            }
+
+            if code_block.fenced && !info.lang.is_empty() && syntax.is_none() {
+                state.warnings.push(Diagnostic::warning(
+                    format!(
+                        "Unknown language \"{}\", highlighting as plain text",
+                        info.lang
+                    ),
+                    Location::new(
+                        start.line,
+                        start.column,
+                        code_block.fence_length + info.lang.len(),
+                    ),
+                ));
+            }
+
+            highlighter::highlight_code(
+                &code_block.literal,
+                syntax,
+                &info,
+                &state.syntaxes,
+                &state.theme,
+                state.highlighting,
+            )?
+        }
+
+        NodeValue::HtmlBlock(html_block) => Tag::Raw(html_block.literal.clone()),
+
+        NodeValue::Paragraph => Tag::P(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Heading(heading) => {
+            let mut children = node
+                .children()
+                .map(|child| iter_nodes(child, state))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let (id, title) = utils::heading_to_slug(&children);
+
+            state.headings.push((heading.level, id.clone(), title));
+
+            children.push(Tag::A(
+                Meta::new()
+                    .with_child(Tag::Text("&sect;".into()))
+                    .with_attrs(vec![
+                        format!("href=\"#heading__{id}\""),
+                        "class=\"section-logo\"".into(),
+                    ]),
+            ));
+
+            let meta = Meta::new()
+                .with_attr(&format!("id=\"heading__{id}\""))
+                .with_children(children);
+
+            match heading.level {
+                1 => Tag::H1(meta),
+                2 => Tag::H2(meta),
+                3 => Tag::H3(meta),
+                4 => Tag::H4(meta),
+                5 => Tag::H5(meta),
+                6 => Tag::H6(meta),
+                _ => unreachable!(),
+            }
+        }
+
+        NodeValue::ThematicBreak => Tag::Hr(Meta::default()),
+
+        NodeValue::FootnoteDefinition(definition) => {
+            let mut children = Vec::new();
+            for child in node.children() {
+                children.push(iter_nodes(child, state)?);
+            }
+
+            let start = node.data.borrow().sourcepos.start;
+            state.definitions.push((
+                definition.clone(),
+                children,
+                Location::new(start.line, start.column, definition.len() + 4),
+            ));
+            Tag::Empty
+        }
+
+        NodeValue::Table(alignments) => {
+            let mut children = Vec::new();
+
+            state.table_counter += 1;
+
+            fn alignment_to_str(alignment: &TableAlignment) -> String {
+                (match alignment {
+                    TableAlignment::Center => "center",
+                    TableAlignment::Left => "left",
+                    TableAlignment::None => "initial",
+                    TableAlignment::Right => "right",
+                })
+                .into()
+            }
+
+            alignments.iter().enumerate().for_each(|(i, alignment)| {
+                state.styles.push(format!(
+                    ".table-{0} td:nth-child({1}), .table-{0} th:nth-child({1}) {{ text-align: {2} }}",
+                    state.table_counter,
+                    i + 1,
+                    alignment_to_str(alignment)
+                ));
+            });
+
+            let table_rows = node.children().collect::<Vec<_>>();
+
+            let header_row = iter_nodes(table_rows[0], state)?;
+
+            for &child in &table_rows[1..] {
+                let el = iter_nodes(child, state)?;
+                children.push(el);
+            }
+
+            let meta = Meta::new()
+                .with_children(vec![
+                    Tag::Thead(Meta::new().with_child(header_row)),
+                    Tag::Tbody(Meta::new().with_children(children)),
+                ])
+                .with_attr(&format!("class=\"table-{}\"", state.table_counter));
+
+            Tag::Table(meta)
+        }
+
+        NodeValue::TableRow(header) => {
+            let mut children = Vec::new();
+            if *header {
+                for table_head in node.children() {
+                    let mut table_children = Vec::new();
+                    for child in table_head.children() {
+                        table_children.push(iter_nodes(child, state)?);
+                    }
+                    children.push(Tag::Th(Meta::new().with_children(table_children)))
+                }
+            } else {
+                for child in node.children() {
+                    children.push(iter_nodes(child, state)?);
+                }
+            }
+
+            Tag::Tr(Meta::new().with_children(children))
+        }
+
+        NodeValue::TableCell => Tag::Td(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Text(text) => {
+            let text = interpolate(text, node, state);
+            state.word_count += text.split_whitespace().collect::<Vec<_>>().len();
+
+            Tag::Text(replacer::replace_emoticons(
+                &replacer::replace_typographer(&text),
+            )?)
+        }
+
+        NodeValue::TaskItem(ch) => {
+            let mut children = Vec::new();
+
+            if let Some(ch) = ch {
+                children.push(utils::char_to_taskitem(*ch))
+            } else {
+                children.push(Tag::Span(
+                    Meta::new().with_attr(r#"class="fa-regular fa-square""#),
+                ))
+            }
+
+            for paragraph in node.children() {
+                for child in paragraph.children() {
+                    children.push(iter_nodes(child, state)?);
+                }
+            }
+
+            Tag::Li(
+                Meta::new()
+                    .with_children(children)
+                    .with_attr("class=\"task-item\""),
+            )
+        }
+
+        NodeValue::SoftBreak => Tag::Br(Meta::default()),
+
+        NodeValue::LineBreak => Tag::Br(Meta::default()),
+
+        NodeValue::Code(code) => Tag::Span(
+            Meta::new()
+                .with_child(Tag::Text(code.literal.clone()))
+                .with_attr("class=\"inline-code\""),
+        ),
+
+        NodeValue::HtmlInline(html_code) => Tag::Raw(html_code.clone()),
+
+        NodeValue::Emph => Tag::I(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Strong => Tag::B(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Strikethrough => Tag::S(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Insert => Tag::U(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Superscript => Tag::Sup(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Link(link) => {
+            let url = interpolate(&link.url, node, state);
+            let mut children = node
+                .children()
+                .map(|child| iter_nodes(child, state))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let Ok(href) = url::Url::parse(&url) {
+                if let Some(domain) = href.domain() {
+                    if domain != state.domain.as_str() {
+                        children.push(Tag::Span(Meta::new().with_attr(
+                            "class=\"fa-solid fa-up-right-from-square href-external\"",
+                        )))
+                    }
+                };
+            }
+
+            Tag::A(Meta::new().with_children(children).with_attrs(vec![
+                format!("href=\"{url}\""),
+                format!("title=\"{}\"", link.title),
+                "target=\"_blank\"".into(),
+                "rel=\"noreferrer\"".into(),
+            ]))
+        }
+
+        NodeValue::Image(img) => {
+            let url = interpolate(&img.url, node, state);
+            let mut attrs = vec![format!("src=\"{url}\"")];
+            let alt = node
+                .first_child()
+                .and_then(|child| match &child.data.borrow().value {
+                    NodeValue::Text(text) => Some(text.clone()),
+                    _ => None,
+                });
+
+            match alt {
+                Some(text) => attrs.push(format!("alt=\"{}\"", text)),
+                None => {
+                    let start = node.data.borrow().sourcepos.start;
+                    state.warnings.push(Diagnostic::warning(
+                        format!("Image \"{url}\" has no alt text"),
+                        Location::new(start.line, start.column, 2),
+                    ));
+                }
+            }
+
+            if !img.title.is_empty() {
+                attrs.push(format!("title=\"{}\"", img.title));
+                Tag::Figure(Meta::new().with_children(vec![
+                    Tag::Img(Meta::new().with_attrs(attrs)),
+                    Tag::Figcaption(Meta::new().with_child(Tag::Text(img.title.clone()))),
+                ]))
+            } else {
+                Tag::Img(Meta::new().with_attrs(attrs))
+            }
+        }
+
+        NodeValue::FootnoteReference(reference) => {
+            let mut tag = String::new();
+            state
+                .footnote_counter
+                .entry(reference.clone())
+                .and_modify(|counter| {
+                    tag = format!(":{}", counter);
+                    *counter += 1;
+                })
+                .or_insert(1);
+
+            Tag::Sup(
+                Meta::new().with_child(Tag::A(
+                    Meta::new()
+                        .with_child(Tag::Text(format!("[{reference}{tag}]")))
+                        .with_attrs(vec![
+                            format!("href=\"#footnote-definition-{reference}\""),
+                            format!("id=\"footnote-reference-{reference}{tag}\""),
+                        ]),
+                )),
+            )
+        }
+
+        NodeValue::ShortCode(short_code) => {
+            Tag::Span(Meta::new().with_child(Tag::Text(short_code.emoji().into())))
+        }
+
+        NodeValue::Subscript => Tag::Sub(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+
+        NodeValue::Highlight => Tag::Mark(
+            Meta::new().with_children(
+                node.children()
+                    .map(|child| iter_nodes(child, state))
+                    .collect::<Result<_, _>>()?,
+            ),
+        ),
+    })
+}
+
+pub fn comrak_options() -> ComrakOptions {
+    ComrakOptions {
+        extension: ComrakExtensionOptions {
+            strikethrough: true,
+            tagfilter: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            superscript: true,
+            header_ids: None,
+            footnotes: true,
+            description_lists: true,
+            front_matter_delimiter: Some(String::from("+++")),
+            shortcodes: true,
+            subscript: true,
+            highlight: true,
+            insert: true,
+        },
+
+        parse: ComrakParseOptions {
+            default_info_string: Some(String::from("txt")),
+            relaxed_tasklist_matching: true,
+            smart: true,
+        },
+
+        render: ComrakRenderOptions {
+            escape: true,
+            ..ComrakRenderOptions::default()
+        },
+    }
+}
+
+/// Parses a markdown document into the arena using the options md2html renders with,
+/// the front matter delimiter being detected from the first line (see [`FrontMatter`]).
+pub fn parse<'a>(arena: &'a Arena<AstNode<'a>>, markdown: &str) -> &'a AstNode<'a> {
+    let mut options = comrak_options();
+
+    match front_matter::Format::detect(markdown) {
+        Some((_, None)) => {
+            // comrak only knows delimited front matter, so the json object is blanked out
+            // (keeping the line numbers) and added back as a front matter node
+            let (raw, body) = markdown.split_at(front_matter::json_len(markdown));
+            let root = comrak::parse_document(
+                arena,
+                &("\n".repeat(raw.matches('\n').count()) + body),
+                &options,
+            );
+
+            options.extension.front_matter_delimiter = Some(String::from(";;;"));
+            if let Some(node) =
+                comrak::parse_document(arena, ";;;\n{}\n;;;\n", &options).first_child()
+            {
+                node.detach();
+                node.data.borrow_mut().value = NodeValue::FrontMatter(raw.to_string());
+                root.prepend(node);
+            }
+
+            root
+        }
+        Some((_, delimiter)) => {
+            options.extension.front_matter_delimiter = delimiter.map(String::from);
+            comrak::parse_document(arena, markdown, &options)
+        }
+        None => comrak::parse_document(arena, markdown, &options),
+    }
+}
+
+/// The front matter of an already parsed markdown document (see [`parse`]).
+pub fn front_matter<'a>(root: &'a AstNode<'a>) -> Result<FrontMatter, Md2HtmlError> {
+    match root
+        .first_child()
+        .map(|node| node.data.borrow().value.clone())
+    {
+        Some(NodeValue::FrontMatter(raw)) => front_matter::parse(&raw),
+        _ => Err(Md2HtmlError::MissingFrontMatter),
+    }
+}
+
+/// Renders an already parsed markdown document (see [`parse`]).
+pub fn render_ast<'a>(
+    root: &'a AstNode<'a>,
+    options: &RenderOptions,
+) -> Result<RenderedDocument, Md2HtmlError> {
+    let mut state = utils::State::default();
+    state.domain.clone_from(&options.domain);
+    state.authors.clone_from(&options.authors);
+    state.theme = options.theme()?.clone();
+    state.highlighting = options.highlighting;
+    state.syntaxes = Arc::clone(&options.syntaxes);
+    state.syntax_aliases.clone_from(&options.syntax_aliases);
+
+    let section = iter_nodes(root, &mut state)?;
+
+    let front_matter = state
+        .front_matter
+        .clone()
+        .ok_or(Md2HtmlError::MissingFrontMatter)?;
+    state.date = front_matter
+        .date
+        .or(options.fallback_date)
+        .ok_or(Md2HtmlError::MissingDate)?;
+    // Only a date given in the front matter schedules a post, not a fallback date in the future
+    state.draft = front_matter.draft || front_matter.date.is_some_and(|date| date > Utc::now());
+
+    let headings = state
+        .headings
+        .iter()
+        .map(|(level, id, title)| Heading {
+            level: *level,
+            id: id.clone(),
+            title: title.clone(),
+        })
+        .collect();
+
+    let mut footnotes = state
+        .definitions
+        .iter()
+        .map(|(label, children, _)| Footnote {
+            label: label.clone(),
+            html: children.iter().map(Tag::to_html).collect(),
+        })
+        .collect::<Vec<_>>();
+    // In the order of the page, the labels being checked to be numbers when rendering it
+    footnotes.sort_by_key(|footnote| footnote.label.parse::<usize>().ok());
+
+    let word_count = state.word_count;
+    let warnings = state.warnings.clone();
+    let date = state.date;
+    let draft = state.draft;
+
+    let html = utils::init(&section, state, options)?;
+
+    Ok(RenderedDocument {
+        html,
+        front_matter,
+        headings,
+        word_count,
+        reading_time: options.reading_time(word_count),
+        footnotes,
+        warnings,
+        date,
+        draft,
+        ast: section,
+    })
+}
+
+/// Renders a markdown document (with its front matter) into a complete html page.
+pub fn render(markdown: &str, options: &RenderOptions) -> Result<RenderedDocument, Md2HtmlError> {
+    let arena = Arena::new();
+    let root = parse(&arena, markdown);
+
+    render_ast(root, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn renders_a_document_with_its_metadata() {
+        let markdown = "---\ntitle: Hello\ntags: [rust]\ndate: 2024-01-01\n---\n# Getting started\n\none two three four\n";
+        let options = RenderOptions {
+            words_per_minute: 2,
+            ..RenderOptions::default()
+        };
+        let document = render(markdown, &options).unwrap();
+
+        assert_eq!(document.front_matter.title, "Hello");
+        assert_eq!(
+            document
+                .headings
+                .iter()
+                .map(|heading| (heading.level, heading.title.as_str()))
+                .collect::<Vec<_>>(),
+            [(1, "Getting started")]
+        );
+        assert_eq!(document.word_count, 6);
+        assert_eq!(document.reading_time, 3);
+        assert!(document.html.contains("Getting started"));
+        assert!(!document.draft);
+    }
+
+    /// Options rendering only the page's authors, with `jdoe` and `rroe` in the authors db.
+    fn authors_options() -> RenderOptions {
+        RenderOptions {
+            authors: toml::from_str(
+                "jdoe = [\"Jane Doe\", \"/jane.png\"]\nrroe = [\"Richard Roe\", \"/richard.png\"]",
+            )
+            .unwrap(),
+            template: Template::new(
+                "page.html",
+                "{% for author in authors %}{{ author.name }} ({{ author.url }});{% endfor %}",
+            )
+            .unwrap(),
+            ..RenderOptions::default()
+        }
+    }
+
+    #[test]
+    fn renders_every_author_of_a_post() {
+        let markdown =
+            "---\ntitle: A\ntags: []\ndate: 2024-01-01\nauthor: jdoe\nauthors: [rroe, jdoe]\n---\nText\n";
+        let document = render(markdown, &authors_options()).unwrap();
+
+        assert!(document.html.ends_with(
+            "Jane Doe (https://localhost/authors/@jdoe);Richard Roe (https://localhost/authors/@rroe);"
+        ));
+
+        let markdown =
+            "---\ntitle: A\ntags: []\ndate: 2024-01-01\nauthors: [jdoe, nobody]\n---\nText\n";
+        assert!(matches!(
+            render(markdown, &authors_options()),
+            Err(Md2HtmlError::MissingAuthor {
+                author,
+                location: Some(diagnostic::Location { line: 5, .. }),
+            }) if author == "nobody"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_the_default_author() {
+        let markdown = "---\ntitle: A\ntags: []\ndate: 2024-01-01\n---\nText\n";
+        let document = render(markdown, &authors_options()).unwrap();
+        assert!(document.html.ends_with("-->\n"));
+
+        let options = RenderOptions {
+            default_author: Some("rroe".to_string()),
+            ..authors_options()
+        };
+        let document = render(markdown, &options).unwrap();
+        assert!(document
+            .html
+            .ends_with("-->\nRichard Roe (https://localhost/authors/@rroe);"));
+
+        let markdown = "---\ntitle: A\ntags: []\ndate: 2024-01-01\nauthor: jdoe\n---\nText\n";
+        let document = render(markdown, &options).unwrap();
+        assert!(document
+            .html
+            .ends_with("-->\nJane Doe (https://localhost/authors/@jdoe);"));
+    }
+
+    #[test]
+    fn dates_posts_from_their_front_matter() {
+        let options = RenderOptions {
+            fallback_date: Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()),
+            template: Template::new(
+                "page.html",
+                "{{ date }}{% if updated %} / {{ updated }}{% endif %}",
+            )
+            .unwrap(),
+            ..RenderOptions::default()
+        };
+
+        let markdown = "+++\ntitle = \"A\"\ntags = []\ndate = 2024-01-31T10:00:00Z\nupdated = \"2024-02-01\"\n+++\nText\n";
+        let document = render(markdown, &options).unwrap();
+        assert_eq!(
+            document.date,
+            Utc.with_ymd_and_hms(2024, 1, 31, 10, 0, 0).unwrap()
+        );
+        assert!(document
+            .html
+            .ends_with("31 January, 2024 /  1 February, 2024"));
+
+        let markdown = "+++\ntitle = \"A\"\ntags = []\n+++\nText\n";
+        let document = render(markdown, &options).unwrap();
+        assert_eq!(document.date, options.fallback_date.unwrap());
+        assert!(document.html.ends_with("-->\n 1 May, 2024"));
+
+        assert!(matches!(
+            render(markdown, &RenderOptions::default()),
+            Err(Md2HtmlError::MissingDate)
+        ));
+    }
+
+    #[test]
+    fn marks_drafts_and_scheduled_posts() {
+        let draft = |front_matter: &str, options: &RenderOptions| {
+            let markdown = format!("---\ntitle: A\ntags: []\n{front_matter}---\nText\n");
+            render(&markdown, options).unwrap().draft
+        };
+        let options = RenderOptions::default();
+
+        assert!(!draft("date: 2024-01-01\n", &options));
+        assert!(draft("date: 2024-01-01\ndraft: true\n", &options));
+        assert!(draft("date: 9999-01-01\n", &options));
+
+        let options = RenderOptions {
+            fallback_date: Some(Utc.with_ymd_and_hms(9999, 1, 1, 0, 0, 0).unwrap()),
+            ..options
+        };
+        assert!(!draft("", &options));
+    }
+
+    #[test]
+    fn loads_custom_themes_by_file_name() {
+        let dir = std::env::temp_dir().join(format!("md2html-themes-{}", std::process::id()));
+        fs::create_dir_all(dir.join("dark")).unwrap();
+        fs::write(
+            dir.join("dark/mini.tmTheme"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\"><dict>\
+            <key>name</key><string>Mini Theme</string><key>settings</key><array><dict>\
+            <key>settings</key><dict><key>foreground</key><string>#FFFFFF</string></dict>\
+            </dict></array></dict></plist>\n",
+        )
+        .unwrap();
+
+        let themes = load_themes(Some(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+        let options = RenderOptions {
+            theme: "mini".to_string(),
+            dark_theme: Some("dracul".to_string()),
+            themes: themes.unwrap(),
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(options.theme().unwrap().name.as_deref(), Some("Mini Theme"));
+        assert!(matches!(
+            options.dark_theme(),
+            Err(Md2HtmlError::UnknownTheme { theme, available })
+                if theme == "dracul"
+                    && available.contains(&"mini".to_string())
+                    && available.contains(&"base16-ocean.dark".to_string())
+        ));
+    }
+
+    #[test]
+    fn caches_custom_syntaxes_until_they_change() {
+        let dir = std::env::temp_dir().join(format!("md2html-syntaxes-{}", std::process::id()));
+        let syntax = dir.join("mini.sublime-syntax");
+        let define = |name: &str, modified: SystemTime| {
+            fs::write(
+                &syntax,
+                format!("%YAML 1.2\n---\nname: {name}\nfile_extensions: [mini]\nscope: source.mini\ncontexts:\n  main:\n    - match: foo\n      scope: keyword.mini\n"),
+            )
+            .and_then(|()| fs::File::options().write(true).open(&syntax))
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+        };
+        let name = || {
+            load_syntaxes(Some(&dir), true)
+                .unwrap()
+                .find_syntax_by_extension("mini")
+                .map(|syntax| syntax.name.clone())
+        };
+        fs::create_dir_all(&dir).unwrap();
+        define("Mini", SystemTime::now());
+
+        assert!(load_syntaxes(Some(&dir), false)
+            .unwrap()
+            .find_syntax_by_name("Mini")
+            .is_some());
+        let written = dir.join(SYNTAX_CACHE).exists();
+        let built = name();
+        let cached = dir.join(SYNTAX_CACHE).exists();
+
+        // Not picked up while the cache is newer than the definition
+        define("Renamed", SystemTime::UNIX_EPOCH);
+        let stale = name();
+        define(
+            "Renamed",
+            SystemTime::now() + std::time::Duration::from_secs(60),
+        );
+        let rebuilt = name();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!written && cached);
+        assert_eq!(built.as_deref(), Some("Mini"));
+        assert_eq!(stale.as_deref(), Some("Mini"));
+        assert_eq!(rebuilt.as_deref(), Some("Renamed"));
+    }
+
+    #[test]
+    fn orders_footnotes_by_label() {
+        let markdown = "---\ntitle: Notes\ntags: [a]\ndate: 2024-01-01\n---\nText[^2] and[^1].\n\n[^2]: Second.\n[^1]: First.\n";
+        let document = render(markdown, &RenderOptions::default()).unwrap();
+
+        let labels = document
+            .footnotes
+            .iter()
+            .map(|footnote| footnote.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["1", "2"]);
+        assert!(document.footnotes[0].html.contains("First."));
+    }
+}
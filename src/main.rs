@@ -1,542 +1,1105 @@
 mod cmd;
-mod highlighter;
-mod html;
-mod replacer;
-mod utils;
+mod config;
+mod serve;
+mod watch;
 
 use std::{
-    fs::{create_dir_all, read_to_string, write},
-    path::PathBuf,
+    collections::{BTreeMap, HashMap},
+    fs::{create_dir_all, metadata, read, read_to_string, write},
+    iter,
+    path::{Component, Path, PathBuf},
+    process::{self, exit},
+    sync::Arc,
 };
 
-use chrono::Utc;
-use clap::Parser;
-use cmd::Command;
+use chrono::{DateTime, Utc};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use cmd::{Cli, Command, Commands, DateFallback, DiagnosticsFormat, FeedMode, HighlightMode};
 use colored::Colorize;
-use comrak::{
-    nodes::{AstNode, ListType, NodeValue, TableAlignment},
-    Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions, ComrakRenderOptions,
+use comrak::Arena;
+use config::Config;
+use md2html::{
+    diagnostic::Diagnostic,
+    feed::{self, Feed, FeedContent, FeedEntry},
+    index, template,
+    utils::{self, len_to_size},
+    Authors, Highlighting, Md2HtmlError, PostAuthor, PostSummary, RenderOptions, Template,
+    HIGHLIGHT_STYLESHEET,
 };
+use syntect::{highlighting::Theme, parsing::SyntaxSet};
 
-use html::{Meta, Tag};
-use once_cell::sync::Lazy;
-use syntect::{
-    highlighting::{Theme, ThemeSet},
-    parsing::SyntaxSet,
-};
-use utils::{len_to_size, must};
-
-static THEME: Lazy<Theme> = Lazy::new(|| {
-    let ts = ThemeSet::load_defaults();
-    ts.themes["base16-eighties.dark"].clone()
-});
-
-static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(|| SyntaxSet::load_defaults_newlines());
-
-fn iter_nodes<'a>(node: &'a AstNode<'a>, state: &mut utils::State) -> Tag {
-    match &node.data.borrow().value {
-        NodeValue::Document => Tag::Section(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+fn get_logger(verbose: bool) -> impl Fn(String) {
+    let f = if verbose {
+        |info: String| println!("{}", format!("[INFO]: {}", info).bright_blue())
+    } else {
+        |_| {}
+    };
+
+    f
+}
+
+/// A successfully converted markdown file.
+struct Output {
+    path: PathBuf,
+    entry: FeedEntry,
+    /// Why the post is left out of the feeds and index pages (ex: `draft`)
+    draft: Option<String>,
+    /// The redirect stubs of the aliases
+    aliases: Vec<PathBuf>,
+    /// Drafts are only written with `--drafts`
+    written: bool,
+}
+
+impl Output {
+    /// The files written for the markdown file.
+    fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        iter::once(&self.path)
+            .chain(&self.aliases)
+            .filter(|_| self.written)
+    }
+}
+
+/// The files written so far along with the markdown file they were generated from.
+type Claimed = HashMap<PathBuf, PathBuf>;
+
+/// Converts a single markdown file and writes the generated html into its directory of `out_root`,
+/// failing if one of its files was already generated from another markdown file.
+fn convert(
+    file_path: &Path,
+    out_root: &Path,
+    cmd: &Command,
+    options: &RenderOptions,
+    claimed: &Claimed,
+    logger: &impl Fn(String),
+) -> Result<Output, Md2HtmlError> {
+    let arena = Arena::new();
 
-        NodeValue::FrontMatter(front_matter) => {
-            let trimmed = front_matter.trim_matches(|c: char| c == '+' || c.is_whitespace());
-            state.front_matter = Some(must(toml::from_str::<utils::FrontMatter>(trimmed)));
-            state.date = Utc::now();
+    let buf = read_to_string(file_path).map_err(Md2HtmlError::io(file_path))?;
+    logger(format!(
+        "Read ({}) markdown file \"{}\"",
+        len_to_size(buf.len())?,
+        file_path.display()
+    ));
 
-            Tag::Empty
+    let root = md2html::parse(&arena, &buf);
+    logger("Parsed markdown file".to_string());
+
+    // The fallback date is only looked up for posts without a date (it may run `git log`)
+    let undated;
+    let options = match md2html::front_matter(root) {
+        Ok(front_matter) if front_matter.date.is_none() => {
+            undated = RenderOptions {
+                fallback_date: fallback_date(cmd, file_path)?,
+                ..options.clone()
+            };
+            &undated
         }
+        _ => options,
+    };
 
-        NodeValue::BlockQuote => Tag::Blockquote(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    let document = md2html::render_ast(root, options)?;
+    logger("Generated HTML AST".into());
 
-        NodeValue::List(list) => {
-            let children = node
-                .children()
-                .map(|child| iter_nodes(child, state))
-                .collect();
+    for warning in &document.warnings {
+        print_diagnostic(cmd, Some(file_path), warning, Some(&buf));
+    }
 
-            match list.list_type {
-                ListType::Bullet => Tag::Ul(Meta::new().with_children(children)),
-                ListType::Ordered => Tag::Ol(Meta::new().with_children(children)),
-            }
+    let out_dir = &file_out_dir(cmd, out_root, file_path);
+    let file_name = utils::text_to_slug(
+        document
+            .front_matter
+            .slug
+            .as_deref()
+            .unwrap_or(&document.front_matter.title),
+    );
+    let out_path = out_dir.join(&file_name).with_extension("html");
+    let mut aliases = Vec::with_capacity(document.front_matter.aliases.len());
+    for alias in &document.front_matter.aliases {
+        let path = alias_path(out_root, out_dir, alias)?;
+
+        let reason = if path == out_path {
+            // The stub would replace the post and redirect to itself
+            Some("it is the post's own page")
+        } else if is_index_page(cmd, options, out_root, &path) {
+            Some("it is a feed, tag page or author page")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(Md2HtmlError::InvalidAlias {
+                alias: alias.clone(),
+                reason,
+            });
         }
 
-        NodeValue::Item(_) => Tag::Li(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+        aliases.push(path);
+    }
 
-        NodeValue::DescriptionList => {
-            let mut children = Vec::new();
-            for item in node.children() {
-                for child in item.children() {
-                    children.push(iter_nodes(child, state));
-                }
+    let authors = document
+        .front_matter
+        .author_keys(options.default_author.as_deref())
+        .into_iter()
+        .map(|key| {
+            let details = options.authors.get(&key);
+            PostAuthor {
+                name: details.map_or_else(|| key.clone(), |details| details.name.clone()),
+                email: details.and_then(|details| details.email.clone()),
+                key,
             }
+        })
+        .collect();
+    let entry = FeedEntry {
+        post: PostSummary {
+            title: document.front_matter.title.clone(),
+            url: page_url(cmd, options, file_path, &file_name),
+            tags: document.front_matter.tags.clone(),
+            authors,
+            date: document.date,
+            updated: document.front_matter.updated,
+            reading_time: document.reading_time,
+        },
+        content: match cmd.feed_content() {
+            FeedMode::Summary => FeedContent::Summary(document.summary()),
+            FeedMode::Full => FeedContent::Full(document.ast.to_html()),
+        },
+    };
 
-            Tag::Dl(Meta::new().with_children(children))
+    let draft = if document.front_matter.draft {
+        Some("draft".to_string())
+    } else if document.draft {
+        Some(format!(
+            "scheduled for {}",
+            document.date.format("%e %B, %Y").to_string().trim()
+        ))
+    } else {
+        None
+    };
+
+    if draft.is_some() && !cmd.drafts {
+        return Ok(Output {
+            path: out_path,
+            entry,
+            draft,
+            aliases,
+            written: false,
+        });
+    }
+
+    // Nothing is written if one of the files cannot be
+    for path in iter::once(&out_path).chain(&aliases) {
+        if let Some(first) = claimed.get(path).filter(|first| *first != file_path) {
+            return Err(Md2HtmlError::OutputCollision {
+                path: path.clone(),
+                first: first.clone(),
+            });
         }
+        check_overwrite(cmd, path)?;
+    }
 
-        NodeValue::DescriptionItem(_) => Tag::Empty,
+    if !out_dir.exists() && !cmd.dry_run {
+        create_dir_all(out_dir).map_err(Md2HtmlError::io(out_dir))?;
+        logger(format!(
+            "Created output directory \"{}\"",
+            out_dir.display()
+        ));
+    }
 
-        NodeValue::DescriptionTerm => Tag::Dt(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    if cmd.output_ast {
+        write_output(
+            cmd,
+            &out_path.with_extension("md.ast"),
+            format!("{:#?}", root),
+        )?;
+        logger(format!(
+            "{} Markdown AST to \"{}\"",
+            write_verb(cmd),
+            out_path.with_extension("md.ast").display()
+        ));
 
-        NodeValue::DescriptionDetails => Tag::Dd(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+        write_output(
+            cmd,
+            &out_path.with_extension("html.ast"),
+            format!("{:#?}", document.ast),
+        )?;
+        logger(format!(
+            "{} HTML AST to \"{}\"",
+            write_verb(cmd),
+            out_path.with_extension("html.ast").display()
+        ));
+    }
 
-        NodeValue::CodeBlock(code_block) => {
-            highlighter::highlight_code(&code_block.literal, &code_block.info, &SYNTAX_SET, &THEME)
-        }
+    write_output(cmd, &out_path, &document.html)?;
 
-        NodeValue::HtmlBlock(html_block) => Tag::Raw(html_block.literal.clone()),
+    logger(format!(
+        "{} ({}) HTML to \"{}\"",
+        write_verb(cmd),
+        len_to_size(document.html.len())?,
+        out_path.display()
+    ));
 
-        NodeValue::Paragraph => Tag::P(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    for alias in &aliases {
+        write_output(
+            cmd,
+            alias,
+            template::redirect_page(&entry.post.url, &document.date),
+        )?;
+        logger(format!(
+            "{} redirect \"{}\" to \"{}\"",
+            write_verb(cmd),
+            alias.display(),
+            entry.post.url
+        ));
+    }
 
-        NodeValue::Heading(heading) => {
-            let mut children = node
-                .children()
-                .map(|child| iter_nodes(child, state))
-                .collect::<Vec<_>>();
-
-            let (id, title) = utils::heading_to_slug(&children);
-
-            state.headings.push((heading.level, id.clone(), title));
-
-            children.push(Tag::A(
-                Meta::new()
-                    .with_child(Tag::Text("&sect;".into()))
-                    .with_attrs(vec![
-                        format!("href=\"#heading__{id}\""),
-                        "class=\"section-logo\"".into(),
-                    ]),
-            ));
-
-            let meta = Meta::new()
-                .with_attr(&format!("id=\"heading__{id}\""))
-                .with_children(children);
-
-            match heading.level {
-                1 => Tag::H1(meta),
-                2 => Tag::H2(meta),
-                3 => Tag::H3(meta),
-                4 => Tag::H4(meta),
-                5 => Tag::H5(meta),
-                6 => Tag::H6(meta),
-                _ => unreachable!(),
-            }
-        }
+    Ok(Output {
+        path: out_path,
+        entry,
+        draft,
+        aliases,
+        written: true,
+    })
+}
 
-        NodeValue::ThematicBreak => Tag::Hr(Meta::default()),
+/// Fails if `path` is an existing html file not generated by md2html, unless `--force` is given.
+fn check_overwrite(cmd: &Command, path: &Path) -> Result<(), Md2HtmlError> {
+    if !cmd.force
+        && path
+            .extension()
+            .is_some_and(|extension| extension == "html")
+        && path.exists()
+        && !read_to_string(path).is_ok_and(|existing| template::is_generated(&existing))
+    {
+        return Err(Md2HtmlError::Overwrite(path.to_path_buf()));
+    }
 
-        NodeValue::FootnoteDefinition(definition) => {
-            let mut children = Vec::new();
-            for child in node.children() {
-                children.push(iter_nodes(child, state));
-            }
+    Ok(())
+}
+
+/// How the verbose logs describe a written file, nothing being written with `--dry-run`.
+fn write_verb(cmd: &Command) -> &'static str {
+    if cmd.dry_run {
+        "Would have written"
+    } else {
+        "Written"
+    }
+}
 
-            state.definitions.push((definition.clone(), children));
-            Tag::Empty
+/// Writes a generated file, creating its directory. Html files not generated by md2html are only
+/// overwritten with `--force` and nothing is written with `--dry-run`.
+fn write_output(
+    cmd: &Command,
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), Md2HtmlError> {
+    check_overwrite(cmd, path)?;
+
+    let exists = path.exists();
+
+    if cmd.dry_run {
+        let message = format!(
+            "Would {} \"{}\"",
+            if exists { "replace" } else { "write" },
+            path.display()
+        );
+        match cmd.diagnostics_format {
+            DiagnosticsFormat::Human => {
+                println!("{}", format!("[DRY-RUN]: {message}").bright_blue())
+            }
+            DiagnosticsFormat::Json => {
+                print_diagnostic(cmd, Some(path), &Diagnostic::info(message), None)
+            }
         }
+        return Ok(());
+    }
 
-        NodeValue::Table(alignments) => {
-            let mut children = Vec::new();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(Md2HtmlError::io(parent))?;
+    }
+    write(path, contents).map_err(Md2HtmlError::io(path))
+}
 
-            state.table_counter += 1;
+/// Whether `path` is one of the feeds, tag pages or author pages written in directory mode.
+fn is_index_page(cmd: &Command, options: &RenderOptions, out_root: &Path, path: &Path) -> bool {
+    let Some(relative) = path
+        .strip_prefix(out_root)
+        .ok()
+        .filter(|_| cmd.input_dir.is_some())
+    else {
+        return false;
+    };
 
-            fn alignment_to_str(alignment: &TableAlignment) -> String {
-                (match alignment {
-                    TableAlignment::Center => "center",
-                    TableAlignment::Left => "left",
-                    TableAlignment::None => "initial",
-                    TableAlignment::Right => "right",
-                })
-                .into()
-            }
+    let is_tag_page = relative.starts_with("tags")
+        && relative.ends_with("index.html")
+        && (2..=3).contains(&relative.components().count());
 
-            alignments.iter().enumerate().for_each(|(i, alignment)| {
-                state.styles.push(format!(
-                    ".table-{0} td:nth-child({1}), .table-{0} th:nth-child({1}) {{ text-align: {2} }}",
-                    state.table_counter,
-                    i + 1,
-                    alignment_to_str(alignment)
-                ));
-            });
+    is_tag_page
+        || relative == Path::new("feed.xml")
+        || relative == Path::new("atom.xml")
+        || options.authors.keys().any(|key| {
+            relative == Path::new(options.author_path(key).trim_matches('/')).join("index.html")
+        })
+}
 
-            let table_rows = node.children().collect::<Vec<_>>();
+/// The redirect stub of an alias, relative to the post's directory unless it starts with `/`.
+fn alias_path(out_root: &Path, out_dir: &Path, alias: &str) -> Result<PathBuf, Md2HtmlError> {
+    let relative = Path::new(alias.trim_start_matches('/'));
+    if relative.as_os_str().is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Md2HtmlError::InvalidAlias {
+            alias: alias.to_string(),
+            reason: "aliases must stay inside the output directory",
+        });
+    }
 
-            let header_row = iter_nodes(table_rows[0], state);
+    let path = if alias.starts_with('/') {
+        out_root.join(relative)
+    } else {
+        out_dir.join(relative)
+    };
 
-            for &child in &table_rows[1..] {
-                let el = iter_nodes(child, state);
-                children.push(el);
-            }
+    Ok(if alias.ends_with('/') {
+        path.join("index.html")
+    } else if path
+        .extension()
+        .is_some_and(|extension| extension == "html")
+    {
+        path
+    } else {
+        let mut path = path.into_os_string();
+        path.push(".html");
+        PathBuf::from(path)
+    })
+}
 
-            let meta = Meta::new()
-                .with_children(vec![
-                    Tag::Thead(Meta::new().with_child(header_row)),
-                    Tag::Tbody(Meta::new().with_children(children)),
-                ])
-                .with_attr(&format!("class=\"table-{}\"", state.table_counter));
+/// The date of the markdown file if its front matter has none.
+fn fallback_date(cmd: &Command, file_path: &Path) -> Result<Option<DateTime<Utc>>, Md2HtmlError> {
+    let modified = || {
+        metadata(file_path)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| Some(modified.into()))
+            .map_err(Md2HtmlError::io(file_path))
+    };
 
-            Tag::Table(meta)
+    match cmd.date_fallback() {
+        DateFallback::Mtime => modified(),
+        DateFallback::Git => {
+            last_commit_date(file_path).map_or_else(modified, |date| Ok(Some(date)))
         }
+        DateFallback::Now => Ok(Some(Utc::now())),
+        DateFallback::Never => Ok(None),
+    }
+}
 
-        NodeValue::TableRow(header) => {
-            let mut children = Vec::new();
-            if *header {
-                for table_head in node.children() {
-                    let mut table_children = Vec::new();
-                    for child in table_head.children() {
-                        table_children.push(iter_nodes(child, state));
-                    }
-                    children.push(Tag::Th(Meta::new().with_children(table_children)))
-                }
-            } else {
-                for child in node.children() {
-                    children.push(iter_nodes(child, state));
-                }
-            }
+/// The date of the last commit touching the file, `None` if it is not committed or git is not installed.
+fn last_commit_date(file_path: &Path) -> Option<DateTime<Utc>> {
+    let dir = match file_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
 
-            Tag::Tr(Meta::new().with_children(children))
-        }
+    let output = process::Command::new("git")
+        .args(["log", "-1", "--format=%cI", "--"])
+        .arg(file_path.file_name()?)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    DateTime::parse_from_rfc3339(String::from_utf8_lossy(&output.stdout).trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
 
-        NodeValue::TableCell => Tag::Td(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+fn read_authors(authors_db: &str, logger: &impl Fn(String)) -> Result<Authors, Md2HtmlError> {
+    let buf = read_to_string(authors_db).map_err(Md2HtmlError::io(authors_db))?;
+    logger(format!(
+        "Read ({}) authors db file \"{}\"",
+        len_to_size(buf.len())?,
+        authors_db
+    ));
 
-        NodeValue::Text(text) => {
-            state.word_count += text.split_whitespace().collect::<Vec<_>>().len();
+    let authors = toml::from_str(&buf).map_err(|e| Md2HtmlError::AuthorsDb(Box::new(e)))?;
+    logger("Parsed authors db file".to_string());
 
-            Tag::Text(replacer::replace_emoticons(&replacer::replace_typographer(
-                &text,
-            )))
-        }
+    Ok(authors)
+}
 
-        NodeValue::TaskItem(ch) => {
-            let mut children = Vec::new();
+fn read_template(cmd: &Command, logger: &impl Fn(String)) -> Result<Template, Md2HtmlError> {
+    let Some(template) = &cmd.template else {
+        return Ok(Template::default());
+    };
 
-            if let Some(ch) = ch {
-                children.push(utils::char_to_taskitem(*ch))
-            } else {
-                children.push(Tag::Span(
-                    Meta::new().with_attr(r#"class="fa-regular fa-square""#),
-                ))
-            }
+    let parsed = Template::from_file(Path::new(template))?;
+    logger(format!("Parsed template \"{template}\""));
 
-            for paragraph in node.children() {
-                for child in paragraph.children() {
-                    children.push(iter_nodes(child, state));
-                }
-            }
+    Ok(parsed)
+}
 
-            Tag::Li(
-                Meta::new()
-                    .with_children(children)
-                    .with_attr("class=\"task-item\"".into()),
-            )
-        }
+fn read_themes(
+    themes_dir: Option<&str>,
+    logger: &impl Fn(String),
+) -> Result<BTreeMap<String, Theme>, Md2HtmlError> {
+    let themes = md2html::load_themes(themes_dir.map(Path::new))?;
+    if let Some(themes_dir) = themes_dir {
+        logger(format!(
+            "Loaded {} themes with the custom ones from \"{themes_dir}\"",
+            themes.len()
+        ));
+    }
 
-        NodeValue::SoftBreak => Tag::Br(Meta::default()),
+    Ok(themes)
+}
 
-        NodeValue::LineBreak => Tag::Br(Meta::default()),
+fn read_syntaxes(cmd: &Command, logger: &impl Fn(String)) -> Result<Arc<SyntaxSet>, Md2HtmlError> {
+    let syntaxes_dir = cmd.syntaxes_dir.as_deref();
+    let syntaxes = md2html::load_syntaxes(syntaxes_dir.map(Path::new), !cmd.dry_run)?;
+    if let Some(syntaxes_dir) = syntaxes_dir {
+        logger(format!(
+            "Loaded {} syntaxes with the custom ones from \"{syntaxes_dir}\"",
+            syntaxes.syntaxes().len()
+        ));
+    }
 
-        NodeValue::Code(code) => Tag::Span(
-            Meta::new()
-                .with_child(Tag::Text(code.literal.clone()))
-                .with_attr("class=\"inline-code\"".into()),
-        ),
+    Ok(syntaxes)
+}
 
-        NodeValue::HtmlInline(html_code) => Tag::Raw(html_code.clone()),
+/// Prints the names of the available themes, one per line.
+fn list_themes(themes_dir: Option<String>, config: Option<&str>) -> Result<(), Md2HtmlError> {
+    let config = Config::load(config, &|_| {})?;
+    let themes = read_themes(themes_dir.as_deref().or(config.themes_dir()), &|_| {})?;
 
-        NodeValue::Emph => Tag::I(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    for name in md2html::theme_names(&themes) {
+        println!("{name}");
+    }
 
-        NodeValue::Strong => Tag::B(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    Ok(())
+}
 
-        NodeValue::Strikethrough => Tag::S(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+/// Writes the stylesheet of the theme when highlighting with classes, shared by all pages.
+fn write_highlight_stylesheet(
+    cmd: &Command,
+    options: &RenderOptions,
+    out_dir: &Path,
+    logger: &impl Fn(String),
+) -> Result<(), Md2HtmlError> {
+    if options.highlighting != Highlighting::Classes {
+        return Ok(());
+    }
 
-        NodeValue::Insert => Tag::U(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    let path = out_dir.join(HIGHLIGHT_STYLESHEET.trim_start_matches('/'));
+    write_output(cmd, &path, md2html::highlight_stylesheet(options)?)?;
+    logger(format!(
+        "{} \"{}\" highlighting stylesheet to \"{}\"",
+        write_verb(cmd),
+        options.theme,
+        path.display()
+    ));
+    if let Some(dark_theme) = &options.dark_theme {
+        logger(format!("Dark mode highlighting with \"{dark_theme}\""));
+    }
 
-        NodeValue::Superscript => Tag::Sup(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    Ok(())
+}
 
-        NodeValue::Link(link) => {
-            let mut children = node
-                .children()
-                .map(|child| iter_nodes(child, state))
-                .collect::<Vec<_>>();
-
-            if let Ok(href) = url::Url::parse(&link.url) {
-                if let Some(domain) = href.domain() {
-                    if domain != state.domain.as_str() {
-                        children.push(Tag::Span(Meta::new().with_attr(
-                            "class=\"fa-solid fa-up-right-from-square href-external\"".into(),
-                        )))
-                    }
-                };
-            }
+fn copy_style_sheet(
+    cmd: &Command,
+    out_dir: &Path,
+    logger: &impl Fn(String),
+) -> Result<(), Md2HtmlError> {
+    let style_sheet = Path::new(cmd.style_sheet());
+    if !style_sheet.exists() {
+        logger(format!(
+            "Style sheet \"{}\" not found, skipping",
+            style_sheet.display()
+        ));
+        return Ok(());
+    }
+
+    let contents = read(style_sheet).map_err(Md2HtmlError::io(style_sheet))?;
+    write_output(cmd, &out_dir.join("styles.css"), contents)?;
+    logger(format!(
+        "Copied style sheet \"{}\" to \"{}\"",
+        style_sheet.display(),
+        out_dir.join("styles.css").display()
+    ));
+
+    Ok(())
+}
 
-            Tag::A(Meta::new().with_children(children).with_attrs(vec![
-                format!("href=\"{}\"", link.url),
-                format!("title=\"{}\"", link.title),
-                "target=\"_blank\"".into(),
-                "rel=\"noreferrer\"".into(),
-            ]))
+/// All the markdown files to be converted.
+fn source_files(cmd: &Command) -> Result<Vec<PathBuf>, Md2HtmlError> {
+    match (&cmd.input_dir, &cmd.file_path) {
+        (Some(input_dir), _) => {
+            utils::collect_markdown_files(Path::new(input_dir)).map_err(Md2HtmlError::io(input_dir))
         }
+        (None, Some(file_path)) => Ok(vec![PathBuf::from(file_path)]),
+        (None, None) => Ok(Vec::new()),
+    }
+}
 
-        NodeValue::Image(img) => {
-            let mut attrs = vec![format!("src=\"{}\"", img.url)];
-            if let NodeValue::Text(text) =
-                &node.children().collect::<Vec<_>>()[0].data.borrow().value
-            {
-                attrs.push(format!("alt=\"{}\"", text))
-            }
+/// The directory a markdown file is written to, mirroring its location in the input directory.
+fn file_out_dir(cmd: &Command, out_dir: &Path, file_path: &Path) -> PathBuf {
+    let relative = match &cmd.input_dir {
+        Some(input_dir) => file_path.strip_prefix(input_dir).unwrap_or(file_path),
+        None => return out_dir.to_path_buf(),
+    };
 
-            if !img.title.is_empty() {
-                attrs.push(format!("title=\"{}\"", img.title));
-                Tag::Figure(Meta::new().with_children(vec![
-                    Tag::Img(Meta::new().with_attrs(attrs)),
-                    Tag::Figcaption(Meta::new().with_child(Tag::Text(img.title.clone()))),
-                ]))
-            } else {
-                Tag::Img(Meta::new().with_attrs(attrs))
+    match relative.parent() {
+        Some(parent) => out_dir.join(parent),
+        None => out_dir.to_path_buf(),
+    }
+}
+
+/// Prints a diagnostic about a markdown file, or about the whole build if `file_path` is `None`.
+fn print_diagnostic(
+    cmd: &Command,
+    file_path: Option<&Path>,
+    diagnostic: &Diagnostic,
+    source: Option<&str>,
+) {
+    match cmd.diagnostics_format {
+        DiagnosticsFormat::Human => println!("{}", diagnostic.render(file_path, source)),
+        DiagnosticsFormat::Json => println!("{}", diagnostic.to_json(file_path)),
+    }
+}
+
+/// The url of a page, mirroring its location in the input directory.
+fn page_url(cmd: &Command, options: &RenderOptions, file_path: &Path, slug: &str) -> String {
+    let path = file_out_dir(cmd, Path::new(""), file_path)
+        .join(slug)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    options.url(&format!("/{path}"))
+}
+
+/// Writes the rss (`feed.xml`) and atom (`atom.xml`) feeds of the converted posts.
+fn write_feeds<'a>(
+    cmd: &Command,
+    options: &RenderOptions,
+    out_dir: &Path,
+    entries: impl Iterator<Item = &'a FeedEntry>,
+    logger: &impl Fn(String),
+) -> Result<(), Md2HtmlError> {
+    // Feed readers cannot resolve root-relative urls, even when serving the blog
+    let entries = entries
+        .cloned()
+        .map(|mut entry| {
+            if entry.post.url.starts_with('/') {
+                entry.post.url = options.absolute_url(&entry.post.url);
             }
-        }
+            entry
+        })
+        .collect::<Vec<_>>();
+    let feed = Feed {
+        title: if options.site_title.is_empty() {
+            options.domain.clone()
+        } else {
+            options.site_title.clone()
+        },
+        description: options.site_description.clone(),
+        domain: options.domain.clone(),
+    };
 
-        NodeValue::FootnoteReference(reference) => {
-            let mut tag = String::new();
-            state
-                .footnote_counter
-                .entry(reference.clone())
-                .and_modify(|counter| {
-                    tag = format!(":{}", counter);
-                    *counter += 1;
-                })
-                .or_insert(1);
-
-            Tag::Sup(
-                Meta::new().with_child(Tag::A(
-                    Meta::new()
-                        .with_child(Tag::Text(format!("[{reference}{tag}]")))
-                        .with_attrs(vec![
-                            format!("href=\"#footnote-definition-{reference}\""),
-                            format!("id=\"footnote-reference-{reference}{tag}\""),
-                        ]),
-                )),
-            )
-        }
+    for (file_name, xml) in [
+        ("feed.xml", feed::rss(&feed, &entries)),
+        ("atom.xml", feed::atom(&feed, &entries)),
+    ] {
+        let out_path = out_dir.join(file_name);
+        write_output(cmd, &out_path, xml)?;
+        logger(format!(
+            "{} feed with {} entries to \"{}\"",
+            write_verb(cmd),
+            entries.len(),
+            out_path.display()
+        ));
+    }
+
+    Ok(())
+}
 
-        NodeValue::ShortCode(short_code) => {
-            Tag::Span(Meta::new().with_child(Tag::Text(short_code.emoji().into())))
+/// Writes the tag pages (`tags/{tag}/index.html`), the tags overview (`tags/index.html`)
+/// and the author pages (`authors/@{author}/index.html` by default), returning the written files.
+fn write_index_pages(
+    cmd: &Command,
+    options: &RenderOptions,
+    out_dir: &Path,
+    posts: &[PostSummary],
+    logger: &impl Fn(String),
+) -> Result<Vec<PathBuf>, Md2HtmlError> {
+    let tags_dir = out_dir.join("tags");
+    // The url path of every page along with what it is generated for
+    let mut pages = vec![(
+        String::from("/tags"),
+        String::from("the tags overview"),
+        index::render_tags_page(posts, options)?,
+    )];
+
+    for (slug, (tag, tagged)) in index::group_by_tag(posts)? {
+        pages.push((
+            format!("/tags/{slug}"),
+            format!("tag \"{tag}\""),
+            index::render_tag_page(&tag, &tagged, options)?,
+        ));
+    }
+    let tag_pages = pages.len();
+
+    for key in options.authors.keys() {
+        pages.push((
+            options.author_path(key),
+            format!("author \"{key}\""),
+            index::render_author_page(key, posts, options)?,
+        ));
+    }
+
+    // Ex: author keys with the same slug (`John Doe` and `john-doe`), or an author named like a tag
+    let mut owners = HashMap::<&str, &str>::new();
+    for (path, owner, _) in &pages {
+        if let Some(first) = owners.insert(path.trim_end_matches('/'), owner) {
+            return Err(Md2HtmlError::PageCollision {
+                path: path.clone(),
+                first: first.to_string(),
+                second: owner.clone(),
+            });
         }
+    }
 
-        NodeValue::Subscript => Tag::Sub(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
-        ),
+    let mut written = Vec::with_capacity(pages.len());
+    for (path, _, html) in pages {
+        let out_path = out_dir.join(path.trim_matches('/')).join("index.html");
+        write_output(cmd, &out_path, html)?;
+        written.push(out_path);
+    }
 
-        NodeValue::Highlight => Tag::Mark(
-            Meta::new().with_children(
-                node.children()
-                    .map(|child| iter_nodes(child, state))
-                    .collect(),
-            ),
+    logger(format!(
+        "{} {} tag pages to \"{}\" and {} author pages",
+        write_verb(cmd),
+        tag_pages,
+        tags_dir.display(),
+        written.len() - tag_pages
+    ));
+
+    Ok(written)
+}
+
+fn report(cmd: &Command, file_path: &Path, result: &Result<Output, Md2HtmlError>) {
+    match result {
+        Ok(_) if cmd.diagnostics_format == DiagnosticsFormat::Json => {}
+        Ok(Output {
+            written: false,
+            draft: Some(reason),
+            ..
+        }) => println!(
+            "{}",
+            format!(
+                "[SKIPPED]: \"{}\" ({reason}, pass `--drafts` to build it)",
+                file_path.display()
+            )
+            .yellow()
         ),
+        Ok(output) => println!(
+            "{}",
+            format!(
+                "[OK]: \"{}\" -> \"{}\"",
+                file_path.display(),
+                output.path.display()
+            )
+            .bright_green()
+        ),
+        Err(e) => {
+            let diagnostic = Diagnostic::from(e);
+            // The source is only needed to show the offending line
+            let source = diagnostic
+                .location
+                .and_then(|_| read_to_string(file_path).ok());
+
+            print_diagnostic(cmd, Some(file_path), &diagnostic, source.as_deref());
+        }
     }
 }
 
-fn get_logger(verbose: bool) -> impl Fn(String) {
-    let f = if verbose {
-        |info: String| println!("{}", format!("[INFO]: {}", info).bright_blue())
-    } else {
-        |_| {}
-    };
+/// The exit code for each category of errors, following the `sysexits.h` conventions.
+fn exit_code(error: &Md2HtmlError) -> i32 {
+    match error {
+        Md2HtmlError::FrontMatter { .. }
+        | Md2HtmlError::MissingFrontMatter
+        | Md2HtmlError::MissingDate
+        | Md2HtmlError::InvalidAlias { .. }
+        | Md2HtmlError::OutputCollision { .. }
+        | Md2HtmlError::PageCollision { .. }
+        | Md2HtmlError::MissingAuthor { .. }
+        | Md2HtmlError::FootnoteLabel { .. } => 65,
+        Md2HtmlError::TooLarge(_) => 66,
+        Md2HtmlError::Highlight(_)
+        | Md2HtmlError::CssMinify(_)
+        | Md2HtmlError::UnknownShortcode(_) => 70,
+        Md2HtmlError::Serve { .. } => 69,
+        Md2HtmlError::Watch(_) => 71,
+        Md2HtmlError::Overwrite(_) => 73,
+        Md2HtmlError::Io { .. } => 74,
+        Md2HtmlError::AuthorsDb(_)
+        | Md2HtmlError::Template(_)
+        | Md2HtmlError::UnknownTheme { .. }
+        | Md2HtmlError::Theme { .. }
+        | Md2HtmlError::Syntaxes { .. }
+        | Md2HtmlError::Config { .. } => 78,
+    }
+}
 
-    f
+fn or_exit<T>(cmd: &Command, result: Result<T, Md2HtmlError>) -> T {
+    result.unwrap_or_else(|e| {
+        print_diagnostic(cmd, None, &Diagnostic::from(&e), None);
+        exit(exit_code(&e))
+    })
 }
 
 fn main() {
-    let arena = Arena::new();
-
-    let cmd = Command::parse();
-
-    let options = ComrakOptions {
-        extension: ComrakExtensionOptions {
-            strikethrough: true,
-            tagfilter: true,
-            table: true,
-            autolink: true,
-            tasklist: true,
-            superscript: true,
-            header_ids: None,
-            footnotes: true,
-            description_lists: true,
-            front_matter_delimiter: Some(String::from("+++")),
-            shortcodes: true,
-            subscript: true,
-            highlight: true,
-            insert: true,
-        },
-
-        parse: ComrakParseOptions {
-            default_info_string: Some(String::from("txt")),
-            relaxed_tasklist_matching: true,
-            smart: true,
-        },
+    let cli = Cli::parse();
 
-        render: ComrakRenderOptions {
-            escape: true,
-            ..ComrakRenderOptions::default()
-        },
+    let (mut cmd, port) = match cli.subcommand {
+        Some(Commands::Serve { port, command }) => (*command, Some(port)),
+        Some(Commands::ListThemes { themes_dir, config }) => {
+            or_exit(&cli.command, list_themes(themes_dir, config.as_deref()));
+            return;
+        }
+        None => (cli.command, None),
     };
 
+    if cmd.highlight == Some(HighlightMode::Inline) && cmd.dark_theme.is_some() {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "`--dark-theme` cannot be used with `--highlight inline` (use `--highlight classes`)",
+            )
+            .exit();
+    }
+
     let logger = get_logger(cmd.verbose);
 
-    let buf = must(read_to_string(&cmd.file_path));
-    logger(format!(
-        "Read ({}) markdown file \"{}\"",
-        must(len_to_size(buf.len())),
-        cmd.file_path
-    ));
+    let mut config = or_exit(&cmd, Config::load(cmd.config.as_deref(), &logger));
+    or_exit(&cmd, config.check_highlight(&cmd));
+    config.merge_into(&mut cmd);
 
-    let root = comrak::parse_document(&arena, &buf, &options);
-    logger("Parsed markdown file".to_string());
+    let options = config.render_options(
+        &cmd,
+        or_exit(&cmd, read_authors(cmd.authors_db(), &logger)),
+        or_exit(&cmd, read_template(&cmd, &logger)),
+        or_exit(&cmd, read_themes(cmd.themes_dir.as_deref(), &logger)),
+        or_exit(&cmd, read_syntaxes(&cmd, &logger)),
+    );
+    or_exit(&cmd, options.theme());
+    or_exit(&cmd, options.dark_theme());
 
-    let mut state = utils::State::default();
-    state.domain.clone_from(&cmd.domain_name);
+    let out_dir =
+        or_exit(&cmd, std::env::current_dir().map_err(Md2HtmlError::io("."))).join(&cmd.out_dir);
 
-    let authors_db = must(std::fs::read_to_string(&cmd.authors_db));
-    logger(format!(
-        "Read ({} bytes) authors db file \"{}\"",
-        must(len_to_size(authors_db.len())),
-        &cmd.authors_db
-    ));
+    or_exit(&cmd, copy_style_sheet(&cmd, &out_dir, &logger));
+    or_exit(
+        &cmd,
+        write_highlight_stylesheet(&cmd, &options, &out_dir, &logger),
+    );
 
-    state.authors = must(toml::from_str(&authors_db));
-    logger("Parsed authors db file".to_string());
+    if let Some(port) = port {
+        or_exit(&cmd, serve::serve(&cmd, options, &out_dir, port, &logger));
+        return;
+    }
 
-    let section = iter_nodes(root, &mut state);
+    if cmd.watch {
+        or_exit(&cmd, watch::watch(&cmd, options, &out_dir, &logger, || {}));
+        return;
+    }
 
-    let out_dir = must(std::env::current_dir()).join(&cmd.out_dir);
-    let file_path = utils::text_to_slug(
-        &must(
-            state
-                .front_matter
-                .clone()
-                .ok_or_else(|| "Front matter not found"),
-        )
-        .title,
-    );
+    if let Some(file_path) = &cmd.file_path {
+        let result = convert(
+            Path::new(file_path),
+            &out_dir,
+            &cmd,
+            &options,
+            &Claimed::new(),
+            &logger,
+        );
+        report(&cmd, Path::new(file_path), &result);
+        if let Err(e) = &result {
+            exit(exit_code(e));
+        }
 
-    let html = utils::init(section, state);
-    logger("Generated HTML AST".into());
+        return;
+    }
+
+    let files = or_exit(&cmd, source_files(&cmd));
+    logger(format!("Found {} markdown files", files.len()));
+
+    let mut failed = 0;
+    let mut skipped = 0;
+    let mut claimed = Claimed::new();
+    let mut status = 0;
+    let mut entries = Vec::new();
+    for file_path in &files {
+        let result = convert(file_path, &out_dir, &cmd, &options, &claimed, &logger);
 
-    let file_path = PathBuf::from(file_path).with_extension("html");
-    let out_path = out_dir.join(file_path);
+        report(&cmd, file_path, &result);
 
-    if !out_path.exists() {
-        must(create_dir_all(&cmd.out_dir));
-        logger(format!("Created output directory \"{}\"", cmd.out_dir));
+        match result {
+            Ok(output) if output.draft.is_none() => {
+                claimed.extend(output.files().map(|path| (path.clone(), file_path.clone())));
+                entries.push(output.entry);
+            }
+            Ok(output) => {
+                claimed.extend(output.files().map(|path| (path.clone(), file_path.clone())));
+                if !output.written {
+                    skipped += 1;
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                if status == 0 {
+                    status = exit_code(&e);
+                }
+            }
+        }
     }
 
-    if cmd.output_ast {
-        must(write(
-            &out_path.with_extension("md.ast"),
-            format!("{:#?}", root),
-        ));
-        logger(format!(
-            "Written Markdown AST to \"{}\"",
-            must(std::env::current_dir())
-                .join(&out_path)
-                .with_extension("md.ast")
-                .display()
-        ));
+    let posts = entries
+        .iter()
+        .map(|entry| entry.post.clone())
+        .collect::<Vec<_>>();
+
+    for result in [
+        write_feeds(&cmd, &options, &out_dir, entries.iter(), &logger),
+        write_index_pages(&cmd, &options, &out_dir, &posts, &logger).map(|_| ()),
+    ] {
+        if let Err(e) = result {
+            print_diagnostic(&cmd, None, &Diagnostic::from(&e), None);
+            if status == 0 {
+                status = exit_code(&e);
+            }
+        }
+    }
 
-        must(write(
-            &out_path.with_extension("html.ast"),
-            format!("{:#?}", html),
-        ));
-        logger(format!(
-            "Written HTML AST to \"{}\"",
-            must(std::env::current_dir())
-                .join(&out_path)
-                .with_extension("html.ast")
-                .display()
-        ));
+    if cmd.diagnostics_format == DiagnosticsFormat::Human {
+        println!(
+            "Converted {} of {} files ({} failed, {} skipped drafts)",
+            files.len() - failed - skipped,
+            files.len(),
+            failed,
+            skipped
+        );
     }
 
-    let html = html.to_html();
+    // Exits with the code of the first failure
+    exit(status);
+}
 
-    must(write(&out_path, &html));
+#[cfg(test)]
+mod tests {
+    use std::fs;
 
-    logger(format!(
-        "Written ({}) HTML to \"{}\"",
-        must(len_to_size(html.len())),
-        must(std::env::current_dir()).join(&out_path).display()
-    ));
+    use super::*;
+
+    #[test]
+    fn resolves_aliases_relative_to_the_post() {
+        let (root, dir) = (Path::new("out"), Path::new("out/blog"));
+
+        assert_eq!(
+            alias_path(root, dir, "old-title").unwrap(),
+            Path::new("out/blog/old-title.html")
+        );
+        assert_eq!(
+            alias_path(root, dir, "2023/old.html").unwrap(),
+            Path::new("out/blog/2023/old.html")
+        );
+    }
+
+    #[test]
+    fn resolves_absolute_aliases_from_the_output_root() {
+        let (root, dir) = (Path::new("out"), Path::new("out/blog"));
+
+        assert_eq!(
+            alias_path(root, dir, "/2023/post/").unwrap(),
+            Path::new("out/2023/post/index.html")
+        );
+        assert_eq!(
+            alias_path(root, dir, "/old").unwrap(),
+            Path::new("out/old.html")
+        );
+    }
+
+    #[test]
+    fn rejects_aliases_leaving_the_output_directory() {
+        let (root, dir) = (Path::new("out"), Path::new("out/blog"));
+
+        for alias in ["../old", "/../old", "a/../../old", "./old", "", "/"] {
+            assert!(
+                matches!(
+                    alias_path(root, dir, alias),
+                    Err(Md2HtmlError::InvalidAlias { alias: invalid, .. }) if invalid == alias
+                ),
+                "{alias:?} is accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn maps_errors_to_sysexits_codes() {
+        let io = Md2HtmlError::io("post.md")(std::io::ErrorKind::NotFound.into());
+        let config = Md2HtmlError::Config {
+            path: PathBuf::from("md2html.toml"),
+            key: Some("wpm".to_string()),
+            message: "must be greater than 0".to_string(),
+        };
+
+        assert_eq!(exit_code(&Md2HtmlError::MissingFrontMatter), 65);
+        assert_eq!(exit_code(&Md2HtmlError::MissingDate), 65);
+        assert_eq!(exit_code(&Md2HtmlError::TooLarge(usize::MAX)), 66);
+        assert_eq!(exit_code(&Md2HtmlError::CssMinify(String::new())), 70);
+        assert_eq!(exit_code(&Md2HtmlError::Watch(String::new())), 71);
+        assert_eq!(
+            exit_code(&Md2HtmlError::Overwrite(PathBuf::from("a.html"))),
+            73
+        );
+        assert_eq!(exit_code(&io), 74);
+        assert_eq!(exit_code(&config), 78);
+    }
+
+    #[test]
+    fn only_writes_drafts_with_the_drafts_flag() {
+        let dir = std::env::temp_dir().join(format!("md2html-drafts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let convert = |front_matter: &str, args: &[&str]| {
+            let file_path = dir.join("post.md");
+            fs::write(
+                &file_path,
+                format!("---\ntitle: Post\ntags: []\n{front_matter}---\nText\n"),
+            )
+            .unwrap();
+            let input_dir = ["-i", dir.to_str().unwrap()];
+            let cmd = Cli::parse_from(["md2html"].iter().chain(args).chain(&input_dir)).command;
+            let output = convert(
+                &file_path,
+                &dir.join("out"),
+                &cmd,
+                &RenderOptions::default(),
+                &Claimed::new(),
+                &|_: String| {},
+            )
+            .unwrap();
+            let exists = output.path.exists();
+            fs::remove_dir_all(dir.join("out")).ok();
+
+            (output.draft, output.written, exists)
+        };
+
+        let published = convert("date: 2024-01-01\n", &[]);
+        let draft = convert("date: 2024-01-01\ndraft: true\n", &[]);
+        let scheduled = convert("date: 9999-01-01\n", &[]);
+        let built = convert("date: 2024-01-01\ndraft: true\n", &["--drafts"]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(published, (None, true, true));
+        assert_eq!(draft, (Some("draft".to_string()), false, false));
+        assert_eq!(
+            scheduled,
+            (
+                Some("scheduled for 1 January, 9999".to_string()),
+                false,
+                false
+            )
+        );
+        assert_eq!(built, (Some("draft".to_string()), true, true));
+    }
+
+    #[test]
+    fn only_overwrites_generated_html_files() {
+        let dir = std::env::temp_dir().join(format!("md2html-overwrite-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("hand-written.html"), "<html></html>").unwrap();
+        fs::write(dir.join("notes.txt"), "Notes").unwrap();
+        fs::write(
+            dir.join("generated.html"),
+            template::redirect_page("/post", &Utc::now()),
+        )
+        .unwrap();
+        let check = |args: &[&str], file: &str| {
+            let cmd = Cli::parse_from(["md2html", "-f", "post.md"].iter().chain(args)).command;
+            check_overwrite(&cmd, &dir.join(file))
+        };
+
+        let results = [
+            check(&[], "hand-written.html"),
+            check(&[], "generated.html"),
+            check(&[], "notes.txt"),
+            check(&[], "missing.html"),
+            check(&["--force"], "hand-written.html"),
+        ];
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            matches!(&results[0], Err(Md2HtmlError::Overwrite(path)) if path.ends_with("hand-written.html"))
+        );
+        assert!(results[1..].iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn mirrors_the_input_directory() {
+        let cmd = Cli::parse_from(["md2html", "-i", "content"]).command;
+        let options = RenderOptions::default();
+
+        assert_eq!(
+            file_out_dir(
+                &cmd,
+                Path::new("out"),
+                Path::new("content/blog/2024/post.md")
+            ),
+            Path::new("out/blog/2024")
+        );
+        assert_eq!(
+            file_out_dir(&cmd, Path::new("out"), Path::new("content/post.md")),
+            Path::new("out")
+        );
+        assert_eq!(
+            page_url(&cmd, &options, Path::new("content/blog/post.md"), "post"),
+            "https://localhost/blog/post"
+        );
+
+        let cmd = Cli::parse_from(["md2html", "-f", "content/blog/post.md"]).command;
+        assert_eq!(
+            file_out_dir(&cmd, Path::new("out"), Path::new("content/blog/post.md")),
+            Path::new("out")
+        );
+    }
+
+    #[test]
+    fn detects_the_index_pages_of_directory_mode() {
+        let cmd = Cli::parse_from(["md2html", "-i", "content"]).command;
+        let options = RenderOptions {
+            authors: toml::from_str("jdoe = [\"Jane Doe\", \"/jane.png\"]").unwrap(),
+            ..RenderOptions::default()
+        };
+        let is_index_page =
+            |path: &str| is_index_page(&cmd, &options, Path::new("out"), Path::new(path));
+
+        assert!(is_index_page("out/feed.xml"));
+        assert!(is_index_page("out/atom.xml"));
+        assert!(is_index_page("out/tags/index.html"));
+        assert!(is_index_page("out/tags/rust/index.html"));
+        assert!(is_index_page("out/authors/@jdoe/index.html"));
+
+        assert!(!is_index_page("out/tags/rust.html"));
+        assert!(!is_index_page("out/tags/rust/old/index.html"));
+        assert!(!is_index_page("out/authors/@someone/index.html"));
+        assert!(!is_index_page("out/blog/feed.xml"));
+
+        let cmd = Cli::parse_from(["md2html", "-f", "post.md"]).command;
+        assert!(!super::is_index_page(
+            &cmd,
+            &options,
+            Path::new("out"),
+            Path::new("out/tags/index.html")
+        ));
+    }
 }
@@ -2,15 +2,17 @@ use fancy_regex::Regex;
 use once_cell::sync::Lazy;
 use phf::phf_map;
 
-use crate::utils::must;
+use crate::Md2HtmlError;
 
-pub static TYPOGRAPHER_REGEX: Lazy<Regex> =
-    Lazy::new(|| must(Regex::new(r#"(\((c|tm|r|p|C|TM|R|P)\))|(\+-|\.{3})"#)));
+pub static TYPOGRAPHER_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(\((c|tm|r|p|C|TM|R|P)\))|(\+-|\.{3})"#).expect("the regex is valid")
+});
 
 pub static EMOTICON_REGEX: Lazy<Regex> = Lazy::new(|| {
-    must(Regex::new(
+    Regex::new(
         r#"(?<=^|\s)(>:\(|>:\-\(|:"\)|:\-"\)|</3|<\\3|:/|:\-/|:'\(|:'\-\(|:,\(|:,\-\(|:\(|:\-\(|<3|\]:\(|\]:\-\(|o:\)|O:\)|o:\-\)|O:\-\)|0:\)|0:\-\)|:'\)|:'\-\)|:,\)|:,\-\)|:'D|:'\-D|:,D|:,\-D|:\*|:\-\*|x\-\)|X\-\)|:\||:\-\||:o|:\-o|:O|:\-O|:@|:\-@|:D|:\-D|:\)|:\-\)|\]:\)|\]:\-\)|:,'\(|:,'\-\(|;\(|;\-\(|:P|:\-P|8\-\)|B\-\)|,:\(|,:\-\(|,:\)|,:\-\)|:s|:\-S|:z|:\-Z|:\$|:\-\$|;\)|;\-\))(?=$|\s)"#,
-    ))
+    )
+    .expect("the regex is valid")
 });
 
 pub static TYPOGRAPHER: phf::Map<&'static str, &'static str> = phf_map! {
@@ -112,20 +114,18 @@ pub fn replace_typographer(text: &str) -> String {
     replaced_text
 }
 
-pub fn replace_emoticons(text: &str) -> String {
+pub fn replace_emoticons(text: &str) -> Result<String, Md2HtmlError> {
     let mut offset = 0;
     let mut replaced_text = text.to_string();
 
-    EMOTICON_REGEX.find_iter(text).for_each(|m| {
-        if let Ok(m) = m {
-            let emoji = must(
-                emojis::get_by_shortcode(EMOTICONS[m.as_str()]).ok_or_else(|| "Emoji not found"),
-            )
+    for m in EMOTICON_REGEX.find_iter(text).flatten() {
+        let shortcode = EMOTICONS[m.as_str()];
+        let emoji = emojis::get_by_shortcode(shortcode)
+            .ok_or_else(|| Md2HtmlError::UnknownShortcode(shortcode.to_string()))?
             .as_str();
-            replaced_text.replace_range(m.start() + offset..m.end() + offset, emoji);
-            offset += emoji.len() - (m.end() - m.start())
-        }
-    });
+        replaced_text.replace_range(m.start() + offset..m.end() + offset, emoji);
+        offset += emoji.len() - (m.end() - m.start())
+    }
 
-    replaced_text
+    Ok(replaced_text)
 }
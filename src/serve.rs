@@ -0,0 +1,193 @@
+use std::{
+    fs::read,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use colored::Colorize;
+use md2html::{Md2HtmlError, RenderOptions};
+use percent_encoding::percent_decode_str;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{cmd::Command, watch::watch};
+
+/// The endpoint polled by the live reload script for the current build version.
+const VERSION_ENDPOINT: &str = "/__md2html/version";
+
+/// Injected into every html page served (never into the generated files),
+/// reloads the page whenever the build version changes.
+const LIVE_RELOAD_SCRIPT: &str = "<script>(()=>{let v=null;setInterval(()=>{fetch('/__md2html/\
+version').then(r=>r.text()).then(t=>{if(v!==null&&v!==t){location.reload();}v=t;}).catch(()=>{});},\
+1000);})();</script>";
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("xml") => "application/xml; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt" | "ast") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Maps a request url to a file in the output directory, rejecting paths that escape it.
+fn resolve(out_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+
+    let mut file_path = out_dir.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => file_path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if file_path.is_dir() {
+        file_path.push("index.html");
+    }
+
+    // Pages are linked to without their extension (ex: `/tags/rust`)
+    if !file_path.is_file() {
+        file_path.set_extension("html");
+    }
+
+    file_path.is_file().then_some(file_path)
+}
+
+fn respond(request: Request, out_dir: &Path, version: &AtomicUsize) {
+    let header = |value: &str| Header::from_bytes("Content-Type", value).ok();
+
+    let response = if request.url() == VERSION_ENDPOINT {
+        Response::from_string(version.load(Ordering::SeqCst).to_string())
+    } else {
+        match resolve(out_dir, request.url()).and_then(|path| Some((read(&path).ok()?, path))) {
+            Some((mut data, path)) => {
+                if path.extension().is_some_and(|ext| ext == "html") {
+                    let position = data
+                        .windows(b"</body>".len())
+                        .rposition(|window| window == b"</body>")
+                        .unwrap_or(data.len());
+                    data.splice(position..position, LIVE_RELOAD_SCRIPT.bytes());
+                }
+
+                let mut response = Response::from_data(data);
+                if let Some(header) = header(content_type(&path)) {
+                    response.add_header(header);
+                }
+
+                response
+            }
+            None => Response::from_string(format!("404 Not Found: {}", request.url()))
+                .with_status_code(404),
+        }
+    };
+
+    if let Err(e) = request.respond(response) {
+        println!("{}", format!("[ERROR]: {e}").bright_red());
+    }
+}
+
+pub fn serve(
+    cmd: &Command,
+    mut options: RenderOptions,
+    out_dir: &Path,
+    port: u16,
+    logger: &impl Fn(String),
+) -> Result<(), Md2HtmlError> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| Md2HtmlError::Serve {
+        address: format!("127.0.0.1:{port}"),
+        message: e.to_string(),
+    })?;
+    println!(
+        "Serving \"{}\" at http://127.0.0.1:{port}",
+        out_dir.display()
+    );
+
+    // The pages link to each other through the server instead of the real domain
+    options.root_relative_urls = true;
+
+    let version = Arc::new(AtomicUsize::new(0));
+
+    {
+        let out_dir = out_dir.to_path_buf();
+        let version = version.clone();
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                respond(request, &out_dir, &version);
+            }
+        });
+    }
+
+    watch(cmd, options, out_dir, logger, || {
+        version.fetch_add(1, Ordering::SeqCst);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn resolves_urls_inside_the_output_directory() {
+        let out_dir = std::env::temp_dir().join(format!("md2html-serve-{}", std::process::id()));
+        for file in [
+            "index.html",
+            "post.html",
+            "tags/rust/index.html",
+            "styles.css",
+        ] {
+            let path = out_dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let resolve = |url: &str| {
+            resolve(&out_dir, url).map(|path| path.strip_prefix(&out_dir).unwrap().to_path_buf())
+        };
+
+        let resolved = [
+            resolve("/"),
+            resolve("/post"),
+            resolve("/post.html?ref=feed#intro"),
+            resolve("/tags/rust/"),
+            resolve("/tags/%72ust"),
+            resolve("/styles.css"),
+            resolve("/missing"),
+            resolve("/../post.html"),
+            resolve("/tags/%2E%2E/post.html"),
+        ];
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!(
+            resolved,
+            [
+                Some(PathBuf::from("index.html")),
+                Some(PathBuf::from("post.html")),
+                Some(PathBuf::from("post.html")),
+                Some(PathBuf::from("tags/rust/index.html")),
+                Some(PathBuf::from("tags/rust/index.html")),
+                Some(PathBuf::from("styles.css")),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+}
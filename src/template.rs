@@ -0,0 +1,339 @@
+use std::{collections::BTreeMap, fmt::Write, fs::read_to_string, path::Path};
+
+use chrono::{DateTime, Utc};
+use minijinja::{escape_formatter, AutoEscape, Environment, Error, Output, State, Value};
+use serde::Serialize;
+
+use crate::{
+    html::Tag,
+    utils::{escape, Author},
+    Highlighting, Md2HtmlError, NavLink, RenderOptions, HIGHLIGHT_STYLESHEET,
+};
+
+/// The original md2html page layout, used when no template is given.
+pub const DEFAULT_TEMPLATE: &str = include_str!("../templates/default.html");
+
+/// A page layout written in the [minijinja](https://docs.rs/minijinja) template language.
+///
+/// The following variables are available to the template:
+/// - `kind`: `post`, `tag`, `tags` or `author`
+/// - `title`, `domain`, `site_title`, `site_description`
+/// - `logo`, `fonts_url` (empty without fonts), `nav`: list of `{ title, url }`
+/// - `tags`: list of `{ name, url }`
+/// - `authors`: list of `{ name, url, avatar, bio, homepage, email, pronouns, socials }`
+/// - `author`, `author_url`, `avatar`: the first author
+/// - `author_bio`, `author_homepage`, `author_email`, `author_pronouns` (`none` if not set),
+///   `author_socials`: map of platform to profile url, of the first author
+/// - `date`, `updated` (`none` if not set), `reading_time` (in minutes)
+/// - `draft`: whether the post is a draft or scheduled for a future date
+/// - `extra`: the front matter fields md2html does not know about (ex: `extra.description`)
+/// - `toc`, `body`, `footnotes`, `styles`: already rendered html (not escaped)
+/// - `highlight_stylesheet`: the url of the code highlighting stylesheet (empty with inline styles)
+#[derive(Debug, Clone)]
+pub struct Template {
+    env: Environment<'static>,
+    /// The name the template is registered under, shown in error messages
+    name: String,
+}
+
+impl Template {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Result<Self, Md2HtmlError> {
+        let name = name.into();
+
+        let mut env = Environment::new();
+        // Escape variables whatever the template's extension
+        env.set_auto_escape_callback(|_| AutoEscape::Html);
+        env.set_formatter(formatter);
+        env.add_template_owned(name.clone(), source.into())
+            .map_err(Md2HtmlError::Template)?;
+
+        Ok(Self { env, name })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Md2HtmlError> {
+        Self::new(
+            path.display().to_string(),
+            read_to_string(path).map_err(Md2HtmlError::io(path))?,
+        )
+    }
+
+    pub(crate) fn render(&self, page: &Page) -> Result<String, Md2HtmlError> {
+        self.env
+            .get_template(&self.name)
+            .and_then(|template| template.render(page))
+            .map_err(Md2HtmlError::Template)
+    }
+}
+
+/// Escapes like the default formatter but leaves `/` as is, so that urls stay readable.
+fn formatter(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    match value.as_str() {
+        Some(text) if !value.is_safe() => {
+            for ch in text.chars() {
+                match ch {
+                    '&' => out.write_str("&amp;")?,
+                    '<' => out.write_str("&lt;")?,
+                    '>' => out.write_str("&gt;")?,
+                    '"' => out.write_str("&quot;")?,
+                    '\'' => out.write_str("&#39;")?,
+                    _ => out.write_char(ch)?,
+                }
+            }
+
+            Ok(())
+        }
+        _ => escape_formatter(out, state, value),
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Self::new("default.html", DEFAULT_TEMPLATE).expect("the built-in template is valid")
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct TagLink {
+    pub name: String,
+    pub url: String,
+}
+
+/// An author of the page along with the url of their page.
+#[derive(Serialize)]
+pub(crate) struct PageAuthor {
+    pub url: String,
+    #[serde(flatten)]
+    pub details: Author,
+}
+
+/// The variables passed to the template.
+#[derive(Serialize)]
+pub(crate) struct Page {
+    pub kind: &'static str,
+    pub title: String,
+    pub domain: String,
+    pub site_title: String,
+    pub site_description: String,
+    pub tags: Vec<TagLink>,
+    pub authors: Vec<PageAuthor>,
+    pub author: String,
+    pub author_url: String,
+    pub avatar: String,
+    pub author_bio: Option<String>,
+    pub author_homepage: Option<String>,
+    pub author_email: Option<String>,
+    pub author_pronouns: Option<String>,
+    pub author_socials: BTreeMap<String, String>,
+    pub date: String,
+    pub updated: Option<String>,
+    pub draft: bool,
+    pub extra: BTreeMap<String, serde_json::Value>,
+    pub reading_time: usize,
+    pub toc: Value,
+    pub body: Value,
+    pub footnotes: Value,
+    pub styles: Value,
+    pub highlight_stylesheet: String,
+    pub logo: String,
+    pub fonts_url: String,
+    pub nav: Vec<NavLink>,
+}
+
+impl Page {
+    /// A page with the site wide variables set and without any post content.
+    pub fn new(kind: &'static str, title: String, options: &RenderOptions) -> Self {
+        let fonts_url = if options.fonts.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "https://fonts.googleapis.com/css2?family={}",
+                options
+                    .fonts
+                    .iter()
+                    .map(|font| font.replace(' ', "+"))
+                    .collect::<Vec<_>>()
+                    .join("&family=")
+            )
+        };
+
+        Self {
+            kind,
+            title,
+            domain: options.domain.clone(),
+            site_title: options.site_title.clone(),
+            site_description: options.site_description.clone(),
+            tags: Vec::new(),
+            authors: Vec::new(),
+            author: String::new(),
+            author_url: String::new(),
+            avatar: String::new(),
+            author_bio: None,
+            author_homepage: None,
+            author_email: None,
+            author_pronouns: None,
+            author_socials: BTreeMap::new(),
+            date: String::new(),
+            updated: None,
+            draft: false,
+            extra: BTreeMap::new(),
+            reading_time: 0,
+            toc: html([]),
+            body: html([]),
+            footnotes: html([]),
+            styles: html([]),
+            highlight_stylesheet: match options.highlighting {
+                Highlighting::Inline => String::new(),
+                Highlighting::Classes => HIGHLIGHT_STYLESHEET.to_string(),
+            },
+            logo: options.logo.clone(),
+            fonts_url,
+            nav: options.nav.clone(),
+        }
+    }
+
+    /// Sets the author variables from the entries of the authors db, keyed by their key.
+    pub fn with_authors(self, authors: &[(String, &Author)], options: &RenderOptions) -> Self {
+        let authors = authors
+            .iter()
+            .map(|(key, author)| PageAuthor {
+                url: options.author_url(key),
+                details: (*author).clone(),
+            })
+            .collect::<Vec<_>>();
+
+        match authors.first() {
+            Some(first) => Self {
+                author: first.details.name.clone(),
+                author_url: first.url.clone(),
+                avatar: first.details.avatar.clone(),
+                author_bio: first.details.bio.clone(),
+                author_homepage: first.details.homepage.clone(),
+                author_email: first.details.email.clone(),
+                author_pronouns: first.details.pronouns.clone(),
+                author_socials: first.details.socials.clone(),
+                authors,
+                ..self
+            },
+            None => self,
+        }
+    }
+}
+
+/// Marks already rendered html as safe so that it is not escaped by the template.
+pub(crate) fn html<'a>(tags: impl IntoIterator<Item = &'a Tag>) -> Value {
+    Value::from_safe_string(tags.into_iter().map(Tag::to_html).collect())
+}
+
+/// A page redirecting to `url`, written for the aliases of a post.
+pub fn redirect_page(url: &str, date: &DateTime<Utc>) -> String {
+    let url = escape(url);
+
+    with_generator_comment(
+        format!(
+            "<!DOCTYPE html>\n\
+            <html lang=\"en\">\n\
+            <head>\n\
+            <meta charset=\"utf-8\">\n\
+            <title>Redirecting to {url}</title>\n\
+            <link rel=\"canonical\" href=\"{url}\">\n\
+            <meta name=\"robots\" content=\"noindex\">\n\
+            <meta http-equiv=\"refresh\" content=\"0; url={url}\">\n\
+            </head>\n\
+            <body><a href=\"{url}\">{url}</a></body>\n\
+            </html>\n"
+        ),
+        Some(date),
+    )
+}
+
+/// The start of the comment marking pages generated by md2html.
+const GENERATOR_COMMENT: &str = "<!-- Generated using `md2html`";
+
+/// Whether an html page was generated by md2html (and can be overwritten).
+pub fn is_generated(page: &str) -> bool {
+    page.lines()
+        .take(3)
+        .any(|line| line.trim_start().starts_with(GENERATOR_COMMENT))
+}
+
+/// Marks the page as generated by md2html, placing the comment after the doctype if there is one.
+///
+/// The comment is dated with `date` if given, so that rebuilding an unchanged page gives the same html.
+pub(crate) fn with_generator_comment(mut page: String, date: Option<&DateTime<Utc>>) -> String {
+    let comment = format!(
+        "\n{GENERATOR_COMMENT} by `Blood Rogue (github.com/blood-rogue)`{}. -->\n",
+        date.map_or_else(String::new, |date| format!(
+            " on {}",
+            date.format("%d/%m/%Y %H:%M:%S")
+        ))
+    );
+
+    let position = match page.get(..9) {
+        Some(start) if start.eq_ignore_ascii_case("<!doctype") => {
+            page.find('>').map_or(0, |i| i + 1)
+        }
+        _ => 0,
+    };
+    page.insert_str(position, &comment);
+
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_custom_templates_escaping_variables() {
+        let template = Template::new(
+            "post.html",
+            "<title>{{ title }}</title><a href=\"{{ nav[0].url }}\">{{ nav[0].title }}</a>{{ body }}",
+        )
+        .unwrap();
+        let page = Page {
+            body: Value::from_safe_string("<p>Hello</p>".to_string()),
+            ..Page::new(
+                "post",
+                "Tom & \"Jerry\"".to_string(),
+                &RenderOptions::default(),
+            )
+        };
+
+        assert_eq!(
+            template.render(&page).unwrap(),
+            "<title>Tom &amp; &quot;Jerry&quot;</title><a href=\"/\">HOME</a><p>Hello</p>"
+        );
+    }
+
+    #[test]
+    fn recognizes_generated_pages() {
+        let page = with_generator_comment("<!DOCTYPE html>\n<html></html>\n".to_string(), None);
+
+        assert!(page.starts_with("<!DOCTYPE html>\n<!-- Generated using `md2html`"));
+        assert!(is_generated(&page));
+        assert!(is_generated(&redirect_page("/post", &Utc::now())));
+        assert!(is_generated(&with_generator_comment(
+            "<p>Partial</p>".to_string(),
+            None
+        )));
+
+        assert!(!is_generated("<!DOCTYPE html>\n<html></html>\n"));
+        assert!(!is_generated(
+            "<!DOCTYPE html>\n<html>\n<body>\n<!-- Generated using `md2html` -->\n</body>\n"
+        ));
+    }
+
+    #[test]
+    fn reports_invalid_templates() {
+        assert!(matches!(
+            Template::new("post.html", "{% if title %}"),
+            Err(Md2HtmlError::Template(_))
+        ));
+
+        let template = Template::new("post.html", "{{ title | missing_filter }}").unwrap();
+        assert!(matches!(
+            template.render(&Page::new("post", String::new(), &RenderOptions::default())),
+            Err(Md2HtmlError::Template(_))
+        ));
+    }
+}
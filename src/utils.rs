@@ -1,39 +1,215 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
-    ops::{AddAssign, Mul},
-    process::exit,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::read_dir,
+    ops::Mul,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-use chrono::{DateTime, Utc};
-use colored::Colorize;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use fancy_regex::Regex;
+use minijinja::Value;
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use serde::{
+    de::{self, value::MapAccessDeserializer, MapAccess, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use syntect::{highlighting::Theme, parsing::SyntaxSet};
+
+use crate::{
+    diagnostic::{Diagnostic, Location},
+    html::{Meta, Tag},
+    template::{self, Page, TagLink},
+    Highlighting, Md2HtmlError, RenderOptions,
+};
 
-use crate::html::{Meta, Tag};
+static NON_ASCII_CHAR: Lazy<Regex> =
+    Lazy::new(|| Regex::new("[^a-z0-9 _]+").expect("the regex is valid"));
+
+pub type Authors = HashMap<String, Author>;
+
+/// An entry of the authors db, either a `["name", "avatar"]` pair or a table.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "AuthorEntry")]
+pub struct Author {
+    /// The display name
+    pub name: String,
+    /// The url of the avatar
+    pub avatar: String,
+    pub bio: Option<String>,
+    pub homepage: Option<String>,
+    /// The profile urls keyed by platform (ex: `github`), which is also the font awesome brand icon
+    pub socials: BTreeMap<String, String>,
+    /// Shown in the feeds
+    pub email: Option<String>,
+    pub pronouns: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthorTable {
+    name: String,
+    avatar: String,
+    bio: Option<String>,
+    homepage: Option<String>,
+    #[serde(default)]
+    socials: BTreeMap<String, String>,
+    email: Option<String>,
+    pronouns: Option<String>,
+}
 
-static NON_ASCII_CHAR: Lazy<Regex> = Lazy::new(|| must(Regex::new("[^a-z0-9 _]+")));
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "a [name, avatar] pair or a table with `name` and `avatar` and optionally `bio`, `homepage`, `socials`, `email` and `pronouns`"
+)]
+enum AuthorEntry {
+    Pair(String, String),
+    Table(AuthorTable),
+}
+
+impl From<AuthorEntry> for Author {
+    fn from(entry: AuthorEntry) -> Self {
+        match entry {
+            AuthorEntry::Pair(name, avatar) => Self {
+                name,
+                avatar,
+                bio: None,
+                homepage: None,
+                socials: BTreeMap::new(),
+                email: None,
+                pronouns: None,
+            },
+            AuthorEntry::Table(table) => Self {
+                name: table.name,
+                avatar: table.avatar,
+                bio: table.bio,
+                homepage: table.homepage,
+                socials: table.socials,
+                email: table.email,
+                pronouns: table.pronouns,
+            },
+        }
+    }
+}
 
-#[derive(Deserialize, Default, Clone)]
+/// The metadata of a post, written in toml (delimited with `+++`), yaml (`---`)
+/// or json (`;;;`, or an object starting the file).
+#[derive(Deserialize, Default, Clone, Debug)]
 pub struct FrontMatter {
     pub title: String,
-    tags: Vec<String>,
-    author: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    /// The co-authors, after `author` if both are given
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// The publication date
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub date: Option<DateTime<Utc>>,
+    /// When the post was last updated
+    #[serde(default, deserialize_with = "deserialize_date")]
+    pub updated: Option<DateTime<Utc>>,
+    /// Unfinished posts are only built with `--drafts`
+    #[serde(default)]
+    pub draft: bool,
+    /// The output file name, instead of the slug of the title
+    pub slug: Option<String>,
+    /// Former urls of the post, redirecting to it
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// The other fields (ex: `description`, `cover`), available to the template as `extra`
+    /// and as `{{ page.extra.key }}` in the body
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Parses an RFC 3339 datetime, a datetime without offset (in UTC) or a date.
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(date) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|datetime| datetime.and_utc())
+}
+
+/// Accepts toml datetimes as well as strings.
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    struct DateVisitor;
+
+    impl<'de> Visitor<'de> for DateVisitor {
+        type Value = DateTime<Utc>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                f,
+                "a datetime or a date (ex: `2024-01-31` or `2024-01-31T10:00:00Z`)"
+            )
+        }
+
+        fn visit_str<E: de::Error>(self, date: &str) -> Result<Self::Value, E> {
+            parse_date(date).ok_or_else(|| E::invalid_value(Unexpected::Str(date), &self))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            let datetime = toml::value::Datetime::deserialize(MapAccessDeserializer::new(map))?;
+            self.visit_str(&datetime.to_string())
+        }
+    }
+
+    deserializer.deserialize_any(DateVisitor).map(Some)
+}
+
+impl FrontMatter {
+    /// The keys of the post's authors in the authors db, without duplicates,
+    /// falling back to the default author if there is one.
+    pub fn author_keys(&self, default_author: Option<&str>) -> Vec<String> {
+        let mut keys = Vec::<String>::new();
+        for key in self.author.iter().chain(&self.authors) {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+
+        if keys.is_empty() {
+            keys.extend(default_author.map(String::from));
+        }
+
+        keys
+    }
 }
 
 #[derive(Default)]
-pub struct State {
+pub(crate) struct State {
     pub table_counter: usize,
     pub front_matter: Option<FrontMatter>,
+    /// The raw front matter, including its delimiters
+    pub front_matter_source: String,
     pub footnote_counter: HashMap<String, usize>,
     pub date: DateTime<Utc>,
-    pub definitions: Vec<(String, Vec<Tag>)>,
+    /// Whether the post is a draft or scheduled for a future date
+    pub draft: bool,
+    pub definitions: Vec<(String, Vec<Tag>, Location)>,
     pub styles: Vec<String>,
     pub word_count: usize,
     pub headings: Vec<(u8, String, String)>,
     pub domain: String,
-    pub authors: HashMap<String, (String, String)>,
+    pub authors: Authors,
+    pub theme: Theme,
+    pub highlighting: Highlighting,
+    pub syntaxes: Arc<SyntaxSet>,
+    pub syntax_aliases: BTreeMap<String, String>,
+    pub warnings: Vec<Diagnostic>,
 }
 
 fn remove_diacritics(string: &str) -> String {
@@ -44,7 +220,7 @@ fn remove_diacritics(string: &str) -> String {
             | 'Ắ' | 'Ẵ' | 'Ẳ' | 'Ȧ' | 'Ǡ' | 'Ä' | 'Ǟ' | 'Ả' | 'Å' | 'Ǻ' | 'Ǎ' | 'Ȁ' | 'Ȃ' | 'Ạ'
             | 'Ậ' | 'Ặ' | 'Ḁ' | 'Ą' | 'Ⱥ' | 'Ɐ' => acc.push('A'),
             'Ꜳ' => acc.push_str("AA"),
-            'Æ' | 'Ǽ' | 'Ǣ' => acc.push_str("A"),
+            'Æ' | 'Ǽ' | 'Ǣ' => acc.push('A'),
             'Ꜵ' => acc.push_str("AO"),
             'Ꜷ' => acc.push_str("AU"),
             'Ꜹ' | 'Ꜻ' => acc.push_str("AV"),
@@ -196,54 +372,113 @@ fn remove_diacritics(string: &str) -> String {
     })
 }
 
-pub fn heading_to_slug(elements: &[Tag]) -> (String, String) {
-    use Tag::*;
-    let mut text = String::new();
-
-    fn iterate(s: &mut String, child: &Tag) {
-        match child {
-            Text(text) => s.add_assign(&text),
-            H1(meta) | H2(meta) | H3(meta) | H4(meta) | H5(meta) | H6(meta) | Blockquote(meta)
-            | Body(meta) | Ol(meta) | Ul(meta) | Li(meta) | Dl(meta) | Dt(meta) | Dd(meta)
-            | Div(meta) | Table(meta) | Tr(meta) | Th(meta) | Td(meta) | Span(meta)
-            | Section(meta) | I(meta) | P(meta) | Code(meta) | Pre(meta) | B(meta) | S(meta)
-            | Sub(meta) | Sup(meta) | Mark(meta) | A(meta) | U(meta) | Link(meta) | Meta(meta)
-            | Hr(meta) | Br(meta) | Img(meta) => {
-                for child in meta.get_children() {
-                    iterate(s, &child)
-                }
-            }
+pub(crate) fn heading_to_slug(elements: &[Tag]) -> (String, String) {
+    let title = elements.iter().map(Tag::text).collect::<String>();
+
+    let text = text_to_slug(&title);
 
-            _ => {}
+    (text, title)
+}
+
+/// The location of the line defining `key` in the raw front matter, written as
+/// `key = ...` (toml), `key: ...` (yaml) or `"key": ...` (json).
+fn front_matter_key_location(front_matter: &str, key: &str) -> Option<Location> {
+    let mut offset = 0;
+    for line in front_matter.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let rest = trimmed
+            .strip_prefix(&format!("\"{key}\""))
+            .or_else(|| trimmed.strip_prefix(key))
+            .map(str::trim_start);
+        if rest.is_some_and(|rest| rest.starts_with('=') || rest.starts_with(':')) {
+            let indent = line.len() - trimmed.len();
+            return Some(Location::from_offset(
+                front_matter,
+                offset + indent,
+                trimmed.trim_end().chars().count(),
+            ));
         }
+        offset += line.len();
     }
 
-    for element in elements {
-        iterate(&mut text, element);
-    }
+    None
+}
 
-    let title = text.clone();
+/// The location of the string `value` in the value of `key` (ex: an element of an array),
+/// or of the whole line if it is not on the same line (ex: a yaml list).
+fn front_matter_value_location(front_matter: &str, key: &str, value: &str) -> Option<Location> {
+    let key_location = front_matter_key_location(front_matter, key)?;
+    let line_start = front_matter
+        .split_inclusive('\n')
+        .take(key_location.line - 1)
+        .map(str::len)
+        .sum::<usize>();
+    let text = front_matter[line_start..].lines().next()?;
+    let value_start = key_location.column - 1 + text[key_location.column - 1..].find(['=', ':'])?;
+
+    // Yaml strings can also be unquoted
+    ["\"", "'", ""]
+        .into_iter()
+        .find_map(|quote| {
+            let start = text[value_start..].find(&format!("{quote}{value}{quote}"))?;
+            Some(Location::from_offset(
+                front_matter,
+                line_start + value_start + start,
+                value.chars().count() + quote.len() * 2,
+            ))
+        })
+        .or(Some(key_location))
+}
 
-    let text = text_to_slug(&text);
+/// Escapes text for html and xml, including attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
 
-    (text, title)
+/// The url of a tag's index page.
+pub fn tag_url(options: &RenderOptions, tag: &str) -> String {
+    options.url(&format!("/tags/{}", text_to_slug(tag)))
 }
 
 pub fn text_to_slug(text: &str) -> String {
     NON_ASCII_CHAR
-        .replace_all(&remove_diacritics(&text).to_lowercase(), "-")
+        .replace_all(&remove_diacritics(text).to_lowercase(), "-")
         .trim_matches('-')
         .to_string()
 }
 
-pub fn init(section: Tag, state: State) -> Tag {
-    let mut footnotes = Vec::with_capacity(state.definitions.len());
-
-    let front_matter = must(state.front_matter.ok_or_else(|| "Missing front-matter"));
+pub(crate) fn init(
+    section: &Tag,
+    state: State,
+    options: &RenderOptions,
+) -> Result<String, Md2HtmlError> {
+    // Ordered by label, whatever the order the definitions are written in
+    let mut footnotes = BTreeMap::new();
+
+    let front_matter = state.front_matter.ok_or(Md2HtmlError::MissingFrontMatter)?;
+
+    for (definition, meta, location) in state.definitions {
+        let label = definition
+            .parse::<usize>()
+            .ok()
+            .filter(|label| *label > 0)
+            .ok_or_else(|| Md2HtmlError::FootnoteLabel {
+                label: definition.clone(),
+                location: Some(location),
+            })?;
 
-    for (definition, meta) in state.definitions {
         let mut references = meta;
-        for i in 0..state.footnote_counter[&definition] {
+        // Definitions without references have no back links
+        for i in 0..state
+            .footnote_counter
+            .get(&definition)
+            .copied()
+            .unwrap_or(0)
+        {
             references.push(Tag::A(
                 Meta::new()
                     .with_child(Tag::Text("↩".into()))
@@ -254,7 +489,7 @@ pub fn init(section: Tag, state: State) -> Tag {
             ))
         }
         footnotes.insert(
-            must(definition.parse::<usize>()) - 1,
+            label,
             Tag::Li(
                 Meta::new().with_child(Tag::Div(
                     Meta::new()
@@ -262,19 +497,7 @@ pub fn init(section: Tag, state: State) -> Tag {
                         .with_attr(&format!("id=\"footnote-definition-{definition}\"")),
                 )),
             ),
-        )
-    }
-
-    let mut tags = Vec::new();
-    for tag in front_matter.tags {
-        tags.push(Tag::A(
-            Meta::new()
-                .with_child(Tag::Text(format!("#{tag}")))
-                .with_attrs(vec![
-                    format!("href=\"https://{}/tags/{tag}\"", state.domain),
-                    "class=\"tag\"".to_string(),
-                ]),
-        ))
+        );
     }
 
     let mut heading_levels = [0; 6];
@@ -342,210 +565,54 @@ pub fn init(section: Tag, state: State) -> Tag {
             .with_attr("class=\"content\""),
     );
 
-    let families = [
-        "Jetbrains Mono:wght@100..800",
-        "Open Sans:wght@300..800",
-        "Mulish:wght@200..700",
-        "Roboto Mono:wght@100",
-    ]
-    .iter()
-    .map(|font| font.replace(" ", "+"))
-    .collect::<Vec<_>>()
-    .join("&family=");
-
-    let minified = must(css_minify::optimizations::Minifier::default().minify(
-        &state.styles.join(""),
-        css_minify::optimizations::Level::Three,
-    ));
-
-    let head = Tag::Head(Meta::new().with_children(Vec::from([
-        Tag::Meta(Meta::new().with_attr("charset=\"utf-8\"")),
-        Tag::Meta(Meta::new().with_attrs(vec![
-            "name=\"viewport\"".to_string(),
-            "content=\"width=device-width, initial-scale=1\"".to_string(),
-        ])),
-        Tag::Meta(Meta::new().with_attrs(vec![
-            "property=\"og:title\"".to_string(),
-            format!("content=\"{}\"", front_matter.title),
-        ])),
-        Tag::Link(Meta::new().with_attrs(vec![
-            "rel=\"icon\"".to_string(),
-            "href=\"/logo.png\"".to_string(),
-        ])),
-        Tag::Link(Meta::new().with_attrs(vec![
-            "rel=\"stylesheet\"".to_string(),
-            format!(
-                "href=\"https://fonts.googleapis.com/css2?family={}\"",
-                families
-            ),
-        ])),
-        Tag::Link(Meta::new().with_attrs(vec![
-            "rel=\"stylesheet\"".to_string(),
-            "href=\"https://unpkg.com/@fortawesome/fontawesome-free/css/all.min.css\"".to_string(),
-        ])),
-        Tag::Link(Meta::new().with_attrs(vec![
-            "rel=\"stylesheet\"".to_string(),
-            "href=\"/styles.css\"".to_string(),
-        ])),
-        Tag::Title(Meta::new().with_child(Tag::Text(front_matter.title.clone()))),
-        Tag::Style(minified),
-    ])));
-
-    let (author, avatar) = must(
-        state
-            .authors
-            .get(
-                &front_matter
-                    .author
-                    .clone()
-                    .unwrap_or_else(|| String::from("blood_rogue")),
-            )
-            .ok_or_else(|| "Author not found"),
-    );
-
-    let nav_bar = Tag::Nav(
-        Meta::new().with_children(Vec::from([
-            Tag::Div(Meta::new().with_children(vec![
-                    Tag::Img(Meta::new().with_attr("src=\"/logo.png\"")),
-                    Tag::Span(
-                        Meta::new()
-                            .with_child(Tag::Text("ΠΑΝΔΑ".to_string()))
-                            .with_attr("id=\"text1\""),
-                    ),
-                    Tag::Span(
-                        Meta::new()
-                            .with_child(Tag::Text("P4ND4".to_string()))
-                            .with_attr("id=\"text2\""),
-                    ),
-                ])),
-            Tag::Ul(
-                Meta::new().with_child(Tag::Div(
-                    Meta::new().with_children(Vec::from([
-                        Tag::Li(
-                            Meta::new().with_child(Tag::A(
-                                Meta::new()
-                                    .with_attr("href=\"/\"")
-                                    .with_child(Tag::Text("HOME".into())),
-                            )),
-                        ),
-                        Tag::Li(
-                            Meta::new().with_child(Tag::A(
-                                Meta::new()
-                                    .with_attr("href=\"/blog\"")
-                                    .with_child(Tag::Text("BLOG".into())),
-                            )),
-                        ),
-                        Tag::Li(
-                            Meta::new().with_child(Tag::A(
-                                Meta::new()
-                                    .with_attr("href=\"/about\"")
-                                    .with_child(Tag::Text("ABOUT".into())),
-                            )),
-                        ),
-                        Tag::Li(
-                            Meta::new().with_child(Tag::A(
-                                Meta::new()
-                                    .with_attr("href=\"/contact\"")
-                                    .with_child(Tag::Text("CONTACT".into())),
-                            )),
-                        ),
-                    ])),
-                )),
-            ),
-        ])),
-    );
-
-    let body = Tag::Body(
-        Meta::new().with_children(Vec::from([
-            Tag::Comment("NAVBAR_START".to_string()),
-            nav_bar,
-            Tag::Comment("NAVBAR_END".to_string()),
-            Tag::Comment("META_CONTAINER_START".to_string()),
-            Tag::H1(
-                Meta::new()
-                    .with_child(Tag::Text(front_matter.title.clone()))
-                    .with_attr("id=\"title\""),
-            ),
-            Tag::Div(Meta::new().with_children(tags)),
-            Tag::Div(
-                Meta::new()
-                    .with_children(vec![
-                        Tag::Img(Meta::new().with_attrs(vec![
-                            format!("src=\"{}\"", avatar),
-                            format!("alt=\"{}\"", author),
-                        ])),
-                        Tag::Span(
-                            Meta::new().with_child(Tag::A(
-                                Meta::new()
-                                    .with_child(Tag::Text(author.to_string()))
-                                    .with_attrs(vec![
-                                        format!(
-                                            "href=\"https://{}/authors/@{}\"",
-                                            state.domain,
-                                            front_matter.author.map_or_else(
-                                                || String::from("me"),
-                                                |value| if value == "blood_rogue" {
-                                                    String::from("me")
-                                                } else {
-                                                    value
-                                                }
-                                            )
-                                        ),
-                                        "target=\"_blank\"".to_string(),
-                                        "rel=\"noreferrer\"".to_string(),
-                                    ]),
-                            )),
-                        ),
-                        Tag::Span(Meta::new().with_child(Tag::Text(format!(
-                            "{} min read &nbsp;&bull;&nbsp; {}",
-                            state.word_count / 120,
-                            state.date.format("%e %B, %Y")
-                        )))),
-                    ])
-                    .with_attr("class=\"meta-container\""),
-            ),
-            Tag::Comment("META_CONTAINER_END".to_string()),
-            Tag::Comment("TOC_START".to_string()),
-            Tag::Details(Meta::new().with_children(vec![
-                Tag::Summary(Meta::new().with_child(Tag::Span(
-                    Meta::new().with_child(Tag::Text("Table of Contents".into())),
-                ))),
-                toc,
-            ])),
-            Tag::Comment("TOC_END".to_string()),
-            Tag::Comment("BLOG_SECTION_START".to_string()),
-            section,
-            Tag::Comment("BLOG_SECTION_END".to_string()),
-            Tag::Comment("FOOTNOTES_START".to_string()),
-            Tag::Section(Meta::new().with_children(vec![
-                Tag::Hr(Meta::default()),
-                Tag::Ol(Meta::new().with_children(footnotes)),
-            ])),
-            Tag::Comment("FOOTNOTES_END".to_string()),
-            Tag::A(
-                Meta::new()
-                    .with_child(Tag::I(
-                        Meta::new().with_attr("class=\"fa-solid fa-chevron-up\""),
-                    ))
-                    .with_attrs(vec![
-                        "href=\"#title\"".to_string(),
-                        "id=\"scroll\"".to_string(),
-                    ]),
-            ),
-            Tag::Raw(format!("<script>{SCRIPT}</script>")),
-        ])),
-    );
-
-    Tag::Doctype(Vec::from([
-        Box::new(Tag::Comment(format!(
-            "Generated using `md2html` by `Blood Rogue (github.com/blood-rogue)` on {}.",
-            state.date.format("%d/%m/%Y %H:%M:%S")
-        ))),
-        Box::new(Tag::Html(Box::new(head), Box::new(body))),
-    ]))
+    let styles = css_minify::optimizations::Minifier::default()
+        .minify(
+            &state.styles.join(""),
+            css_minify::optimizations::Level::Three,
+        )
+        .map_err(|e| Md2HtmlError::CssMinify(e.to_string()))?;
+
+    let authors = front_matter
+        .author_keys(options.default_author.as_deref())
+        .into_iter()
+        .map(|key| match state.authors.get(&key) {
+            Some(author) => Ok((key, author)),
+            None => Err(Md2HtmlError::MissingAuthor {
+                location: ["author", "authors"].into_iter().find_map(|field| {
+                    front_matter_value_location(&state.front_matter_source, field, &key)
+                }),
+                author: key,
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let page = options.template.render(&Page {
+        tags: front_matter
+            .tags
+            .iter()
+            .map(|tag| TagLink {
+                name: tag.clone(),
+                url: tag_url(options, tag),
+            })
+            .collect(),
+        date: state.date.format("%e %B, %Y").to_string(),
+        draft: state.draft,
+        extra: front_matter.extra.clone(),
+        updated: front_matter
+            .updated
+            .map(|updated| updated.format("%e %B, %Y").to_string()),
+        reading_time: options.reading_time(state.word_count),
+        toc: template::html([&toc]),
+        body: template::html([section]),
+        footnotes: template::html(footnotes.values()),
+        styles: Value::from_safe_string(styles),
+        ..Page::new("post", front_matter.title.clone(), options).with_authors(&authors, options)
+    })?;
+
+    Ok(template::with_generator_comment(page, Some(&state.date)))
 }
 
-pub fn char_to_taskitem(ch: char) -> Tag {
+pub(crate) fn char_to_taskitem(ch: char) -> Tag {
     let (icon, color) = match ch {
         'x' => ("square-check", "limegreen"),
         '-' => ("square-minus", "grey"),
@@ -560,39 +627,124 @@ pub fn char_to_taskitem(ch: char) -> Tag {
     ]))
 }
 
-pub fn must<T, E: Debug>(res: Result<T, E>) -> T {
-    match res {
-        Ok(t) => t,
-        Err(e) => {
-            println!("{}", format!("[ERROR]: {:#?}", e).bright_red());
-            exit(0);
+pub fn collect_markdown_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let mut entries = read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            files.extend(collect_markdown_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            files.push(path);
         }
     }
+
+    Ok(files)
 }
 
-pub fn len_to_size(len: usize) -> Result<String, String> {
+pub fn len_to_size(len: usize) -> Result<String, Md2HtmlError> {
     let (size, unit) = match len {
         0..=1023 => (len, "bytes"),
         1024..=1048575 => (len / 1024, "kb"),
         1048576..=1073741823 => (len / 1048576, "mb"),
-        _ => return Err("Too big to parse".into()),
+        _ => return Err(Md2HtmlError::TooLarge(len)),
     };
 
     Ok(format!("{} {}", size, unit))
 }
 
-const SCRIPT: &'static str = "class X{constructor(a){this.a=a;this.b=a.querySelector\
-('summary');this.c=a.querySelector('.content');this.d=null;this.e=false;this.f=false;\
-this.b.addEventListener('click',(e)=>this.A(e));}A(e){e.preventDefault();this.a.style.\
-overflow='hidden';if(this.e||!this.a.open){this.C();}else if(this.f||this.a.open){this.\
-B();}}B(){this.e=true;const x=`${this.a.offsetHeight}px`;const y=`${this.b.offsetHeight}px`;\
-if(this.d){this.d.cancel();}this.d=this.a.animate({height:[x,y]},{duration:400,easing:'ease'})\
-;this.d.onfinish=()=>this.E(false);this.d.oncancel=()=>this.e=false;}C(){this.a.style.height=`$\
-{this.a.offsetHeight}px`;this.a.open=true;window.requestAnimationFrame(()=>this.D());}D(){this.f\
-=true;const x=`${this.a.offsetHeight}px`;const y=`${this.b.offsetHeight+this.c.offsetHeight}px`;\
-if(this.d){this.d.cancel();}this.d=this.a.animate({height:[x,y]},{duration:400,easing:'ease'});\
-this.d.onfinish=()=>this.E(true);this.d.oncancel=()=>this.f=false;}E(open){this.a.open=open;\
-this.d=null;this.e=false;this.f=false;this.a.style.height=this.a.style.overflow='';}}new X\
-(document.querySelectorAll('details')[0]);(()=>{const a=document.querySelector('#scroll');const B=()\
-=>{window.scrollY>window.innerHeight?a.classList.add('show'):a.classList.remove('show');};window.\
-addEventListener('scroll',B);})();";
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn parses_datetimes_and_dates() {
+        let date = |date: &str| parse_date(date).map(|date| date.to_rfc3339());
+
+        assert_eq!(
+            date("2024-01-31T10:00:00+02:00").as_deref(),
+            Some("2024-01-31T08:00:00+00:00")
+        );
+        assert_eq!(
+            date("2024-01-31T10:00:00").as_deref(),
+            Some("2024-01-31T10:00:00+00:00")
+        );
+        assert_eq!(
+            date("2024-01-31 10:00:00.5").as_deref(),
+            Some("2024-01-31T10:00:00.500+00:00")
+        );
+        assert_eq!(
+            date("2024-01-31").as_deref(),
+            Some("2024-01-31T00:00:00+00:00")
+        );
+        assert_eq!(date("31/01/2024"), None);
+        assert_eq!(date("2024-02-30"), None);
+    }
+
+    #[test]
+    fn reads_authors_as_pairs_or_tables() {
+        let authors = toml::from_str::<Authors>(
+            "jdoe = [\"Jane Doe\", \"/jane.png\"]\n\n\
+            [rroe]\nname = \"Richard Roe\"\navatar = \"/richard.png\"\nbio = \"Writes\"\n\
+            socials = { github = \"https://github.com/rroe\" }\n",
+        )
+        .unwrap();
+
+        assert_eq!(authors["jdoe"].name, "Jane Doe");
+        assert_eq!(authors["jdoe"].avatar, "/jane.png");
+        assert!(authors["jdoe"].socials.is_empty());
+        assert_eq!(authors["rroe"].name, "Richard Roe");
+        assert_eq!(authors["rroe"].bio.as_deref(), Some("Writes"));
+        assert_eq!(authors["rroe"].socials["github"], "https://github.com/rroe");
+        assert_eq!(authors["rroe"].email, None);
+    }
+
+    #[test]
+    fn rejects_invalid_authors() {
+        for source in [
+            "jdoe = [\"Jane Doe\"]",
+            "[jdoe]\nname = \"Jane Doe\"",
+            "[jdoe]\nname = \"Jane Doe\"\navatar = \"/jane.png\"\ntwitter = \"@jdoe\"",
+        ] {
+            assert!(
+                toml::from_str::<Authors>(source).is_err(),
+                "{source:?} is accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn collects_markdown_files_recursively_in_path_order() {
+        let dir = std::env::temp_dir().join(format!("md2html-collect-{}", std::process::id()));
+        for file in [
+            "b.md",
+            "a.md",
+            "notes.txt",
+            "a.md.bak",
+            "posts/2024/c.md",
+            "posts/d.md",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let files = collect_markdown_files(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let files = files
+            .unwrap()
+            .into_iter()
+            .map(|file| file.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            ["a.md", "b.md", "posts/2024/c.md", "posts/d.md"].map(PathBuf::from)
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{canonicalize, remove_file},
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use md2html::{diagnostic::Diagnostic, Md2HtmlError, RenderOptions};
+use notify::{Event, RecursiveMode, Watcher};
+
+use crate::{
+    cmd::Command, convert, copy_style_sheet, print_diagnostic, read_authors, read_template, report,
+    source_files, write_feeds, write_index_pages, Claimed, Output,
+};
+
+/// How long to wait for more events before rebuilding, so that a single save
+/// (which usually emits several events) only triggers one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+struct Site<'a, L: Fn(String)> {
+    cmd: &'a Command,
+    options: RenderOptions,
+    out_dir: &'a Path,
+    logger: &'a L,
+    /// Every known markdown file along with its output, `None` if the last conversion failed.
+    pages: HashMap<PathBuf, Option<Output>>,
+    /// The tag and author pages written by the last build
+    index_pages: HashSet<PathBuf>,
+}
+
+impl<'a, L: Fn(String)> Site<'a, L> {
+    fn rebuild(&mut self, file_path: &Path) {
+        let claimed = self
+            .pages
+            .iter()
+            .filter(|(source, _)| *source != file_path)
+            .flat_map(|(source, output)| {
+                output
+                    .iter()
+                    .flat_map(Output::files)
+                    .map(move |path| (path.clone(), source.clone()))
+            })
+            .collect::<Claimed>();
+
+        let result = convert(
+            file_path,
+            self.out_dir,
+            self.cmd,
+            &self.options,
+            &claimed,
+            self.logger,
+        );
+        report(self.cmd, file_path, &result);
+
+        // Includes the files of a post turned into a draft
+        if let (Some(Some(old)), Ok(new)) = (self.pages.get(file_path), &result) {
+            let current = new.files().collect::<HashSet<_>>();
+            for stale in old.files().filter(|path| !current.contains(path)) {
+                if remove_file(stale).is_ok() {
+                    (self.logger)(format!("Removed stale output \"{}\"", stale.display()));
+                }
+            }
+        }
+
+        self.pages.insert(file_path.to_path_buf(), result.ok());
+    }
+
+    fn remove(&mut self, file_path: &Path) {
+        if let Some(Some(output)) = self.pages.remove(file_path) {
+            for path in output.files() {
+                if remove_file(path).is_ok() {
+                    (self.logger)(format!("Removed output \"{}\"", path.display()));
+                }
+            }
+        }
+    }
+
+    /// Reloads the authors db, returning the pages affected by the changed entries.
+    fn reload_authors(&mut self) -> Result<Vec<PathBuf>, Md2HtmlError> {
+        let authors = read_authors(self.cmd.authors_db(), self.logger)?;
+
+        let changed = authors
+            .keys()
+            .chain(self.options.authors.keys())
+            .filter(|key| authors.get(*key) != self.options.authors.get(*key))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        self.options.authors = authors;
+
+        Ok(self
+            .pages
+            .iter()
+            .filter(|(_, output)| {
+                output.as_ref().is_none_or(|output| {
+                    output
+                        .entry
+                        .post
+                        .authors
+                        .iter()
+                        .any(|author| changed.contains(&author.key))
+                })
+            })
+            .map(|(file_path, _)| file_path.clone())
+            .collect())
+    }
+
+    /// Regenerates the feeds, tag and author pages of a directory from the pages converted successfully.
+    fn write_indexes(&mut self) {
+        if self.cmd.input_dir.is_none() {
+            return;
+        }
+
+        let entries = self
+            .pages
+            .values()
+            .flatten()
+            .filter(|output| output.draft.is_none())
+            .map(|output| &output.entry);
+        if let Err(e) = write_feeds(
+            self.cmd,
+            &self.options,
+            self.out_dir,
+            entries.clone(),
+            self.logger,
+        ) {
+            print_diagnostic(self.cmd, None, &Diagnostic::from(&e), None);
+        }
+
+        let posts = entries.map(|entry| entry.post.clone()).collect::<Vec<_>>();
+        match write_index_pages(self.cmd, &self.options, self.out_dir, &posts, self.logger) {
+            Ok(written) => {
+                let written = written.into_iter().collect::<HashSet<_>>();
+                for stale in self.index_pages.difference(&written) {
+                    if remove_file(stale).is_ok() {
+                        (self.logger)(format!("Removed stale index page \"{}\"", stale.display()));
+                    }
+                }
+                self.index_pages = written;
+            }
+            Err(e) => print_diagnostic(self.cmd, None, &Diagnostic::from(&e), None),
+        }
+    }
+
+    /// Reloads the page template, returning every page as they all use it.
+    fn reload_template(&mut self) -> Result<Vec<PathBuf>, Md2HtmlError> {
+        self.options.template = read_template(self.cmd, self.logger)?;
+
+        Ok(self.pages.keys().cloned().collect())
+    }
+}
+
+/// Resolves a path that may not exist (anymore) by canonicalizing its parent directory.
+fn absolute(path: &Path) -> Result<PathBuf, Md2HtmlError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Md2HtmlError::Watch(format!("Invalid path \"{}\"", path.display())))?;
+
+    Ok(canonicalize(parent)
+        .map_err(Md2HtmlError::io(parent))?
+        .join(file_name))
+}
+
+/// Maps a changed path back to the markdown file as it was given on the command line.
+fn to_source(cmd: &Command, root: &Path, path: &Path) -> Option<PathBuf> {
+    match &cmd.input_dir {
+        Some(input_dir) => {
+            let relative = path.strip_prefix(root).ok()?;
+            (path.extension()? == "md").then(|| Path::new(input_dir).join(relative))
+        }
+        None => cmd
+            .file_path
+            .as_ref()
+            .filter(|_| path == root)
+            .map(PathBuf::from),
+    }
+}
+
+fn collect_paths(event: notify::Result<Event>, changed: &mut HashSet<PathBuf>) {
+    match event {
+        Ok(event) if !event.kind.is_access() => changed.extend(event.paths),
+        Ok(_) => {}
+        Err(e) => println!("{}", format!("[ERROR]: {e}").bright_red()),
+    }
+}
+
+pub fn watch(
+    cmd: &Command,
+    options: RenderOptions,
+    out_dir: &Path,
+    logger: &impl Fn(String),
+    on_rebuild: impl Fn(),
+) -> Result<(), Md2HtmlError> {
+    let mut site = Site {
+        cmd,
+        options,
+        out_dir,
+        logger,
+        pages: HashMap::new(),
+        index_pages: HashSet::new(),
+    };
+
+    let start = Instant::now();
+    let files = source_files(cmd)?;
+    for file_path in &files {
+        site.rebuild(file_path);
+    }
+    site.write_indexes();
+    logger(format!(
+        "Built {} files in {:.2?}",
+        files.len(),
+        start.elapsed()
+    ));
+
+    let authors_db = absolute(Path::new(cmd.authors_db()))?;
+    let style_sheet = absolute(Path::new(cmd.style_sheet()))?;
+    let template = cmd
+        .template
+        .as_ref()
+        .map(|template| absolute(Path::new(template)))
+        .transpose()?;
+
+    // Editors usually save by replacing the file, which drops watches on the file
+    // itself, so the parent directories are watched instead.
+    let mut watched = HashMap::new();
+    for path in [Some(&authors_db), Some(&style_sheet), template.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(parent) = path.parent() {
+            watched
+                .entry(parent.to_path_buf())
+                .or_insert(RecursiveMode::NonRecursive);
+        }
+    }
+
+    let root = match (&cmd.input_dir, &cmd.file_path) {
+        (Some(input_dir), _) => {
+            let root = canonicalize(input_dir).map_err(Md2HtmlError::io(input_dir))?;
+            watched.insert(root.clone(), RecursiveMode::Recursive);
+            root
+        }
+        (None, Some(file_path)) => {
+            let root = absolute(Path::new(file_path))?;
+            if let Some(parent) = root.parent() {
+                watched
+                    .entry(parent.to_path_buf())
+                    .or_insert(RecursiveMode::NonRecursive);
+            }
+            root
+        }
+        (None, None) => unreachable!("Either an input file or directory is required"),
+    };
+
+    let (tx, rx) = channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).map_err(|e| Md2HtmlError::Watch(e.to_string()))?;
+    for (dir, mode) in &watched {
+        watcher
+            .watch(dir, *mode)
+            .map_err(|e| Md2HtmlError::Watch(e.to_string()))?;
+        logger(format!("Watching \"{}\"", dir.display()));
+    }
+
+    println!("Watching for changes, press Ctrl+C to stop");
+
+    while let Ok(event) = rx.recv() {
+        let mut changed = HashSet::new();
+        collect_paths(event, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect_paths(event, &mut changed);
+        }
+
+        let start = Instant::now();
+
+        let style_sheet_changed = changed.contains(&style_sheet);
+        if style_sheet_changed {
+            if let Err(e) = copy_style_sheet(cmd, out_dir, logger) {
+                let path = Path::new(cmd.style_sheet());
+                print_diagnostic(cmd, Some(path), &Diagnostic::from(&e), None);
+            }
+        }
+
+        let mut affected = changed
+            .iter()
+            .filter_map(|path| to_source(cmd, &root, path))
+            .collect::<HashSet<_>>();
+
+        // The author pages list every author, even those without posts
+        let mut authors_reloaded = false;
+        if changed.contains(&authors_db) {
+            match site.reload_authors() {
+                Ok(pages) => {
+                    affected.extend(pages);
+                    authors_reloaded = true;
+                }
+                Err(e) => {
+                    let path = Path::new(cmd.authors_db());
+                    print_diagnostic(cmd, Some(path), &Diagnostic::from(&e), None);
+                }
+            }
+        }
+
+        if let Some(template) = template.as_ref().filter(|path| changed.contains(*path)) {
+            match site.reload_template() {
+                Ok(pages) => affected.extend(pages),
+                Err(e) => print_diagnostic(cmd, Some(template), &Diagnostic::from(&e), None),
+            }
+        }
+
+        let mut affected = affected.into_iter().collect::<Vec<_>>();
+        affected.sort();
+
+        for file_path in &affected {
+            if file_path.exists() {
+                site.rebuild(file_path);
+            } else {
+                site.remove(file_path);
+            }
+        }
+
+        if !affected.is_empty() || authors_reloaded {
+            site.write_indexes();
+        }
+
+        if !affected.is_empty() {
+            logger(format!(
+                "Rebuilt {} files in {:.2?}",
+                affected.len(),
+                start.elapsed()
+            ));
+        }
+
+        if style_sheet_changed || authors_reloaded || !affected.is_empty() {
+            on_rebuild();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::cmd::Cli;
+
+    #[test]
+    fn maps_changed_markdown_files_to_their_source() {
+        let cmd = Cli::parse_from(["md2html", "-i", "content"]).command;
+        let root = Path::new("/blog/content");
+
+        assert_eq!(
+            to_source(&cmd, root, Path::new("/blog/content/2024/post.md")),
+            Some(PathBuf::from("content/2024/post.md"))
+        );
+        assert_eq!(
+            to_source(&cmd, root, Path::new("/blog/content/post.md.swp")),
+            None
+        );
+        assert_eq!(
+            to_source(&cmd, root, Path::new("/blog/other/post.md")),
+            None
+        );
+    }
+
+    #[test]
+    fn maps_the_watched_file_to_its_source() {
+        let cmd = Cli::parse_from(["md2html", "-f", "./post.md"]).command;
+        let root = Path::new("/blog/post.md");
+
+        assert_eq!(
+            to_source(&cmd, root, root),
+            Some(PathBuf::from("./post.md"))
+        );
+        assert_eq!(to_source(&cmd, root, Path::new("/blog/other.md")), None);
+    }
+}